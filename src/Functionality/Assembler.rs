@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::Preprocessor::Preprocessor;
+use super::TagResolution::SymbolTable;
+use super::{CodeGen, IsaExtensions, Parser, Pipeline, Pseudo};
+
+use crate::Structures::Data::{is_data_directive, Data};
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::{AssemblyWarning, MetadataError, SyntaxError};
+use crate::Structures::Instruction::{Instruction, IsaLevel};
+use crate::Structures::InstructionTable::InstructionTable;
+use crate::Structures::RELFHeaders::Sections;
+use crate::Structures::Register::Register;
+use crate::Structures::Statement::{Operand, OperandKind, Statement, StatementKind};
+
+/**
+ *  An assembly session: collects one or more sources and assembles them
+ *  into an in-memory program
+ */
+#[derive(Debug, Default)]
+pub struct Assembler {
+    sources: Vec<(String, String)>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, i64)>,
+    isa: IsaLevel,
+    table: InstructionTable,
+}
+
+/**
+ *  The result of an assembly run. `code` holds the encoded words starting at
+ *  `code_base_addr`, `data` the dense data segment starting at `data_base_addr`.
+ *  `warnings` holds every non-fatal diagnostic found along the way
+ */
+#[derive(Debug)]
+pub struct AssembledProgram {
+    pub code_base_addr: u32,
+    pub code: Vec<u32>,
+    pub data_base_addr: u32,
+    pub data: Vec<u8>,
+    pub symbols: HashMap<String, u32>,
+    pub entry: u32,
+    pub listing: Vec<String>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Assembler::default()
+    }
+
+    /**
+     *  Adds a source to the session. Sources are assembled in the order they were added,
+     *  as if they were a single file
+     */
+    pub fn add_source(&mut self, name: &str, text: &str) -> &mut Self {
+        self.sources.push((name.to_string(), text.to_string()));
+        self
+    }
+
+    /**
+     *  Adds a directory to search for `.include`d files. Directories are searched in the order
+     *  they were added, after the directory of the including file
+     */
+    pub fn include_dir(&mut self, dir: &str) -> &mut Self {
+        self.include_dirs.push(PathBuf::from(dir));
+        self
+    }
+
+    /**
+     *  Predefines a symbolic constant, as if the sources started with `.equ name, value`
+     */
+    pub fn define(&mut self, name: &str, value: i64) -> &mut Self {
+        self.defines.push((name.to_string(), value));
+        self
+    }
+
+    /**
+     *  Selects the instruction set level to assemble for. Defaults to MIPS I
+     */
+    pub fn isa(&mut self, level: IsaLevel) -> &mut Self {
+        self.isa = level;
+        self
+    }
+
+    /**
+     *  Adds the instruction definitions of an extension file, TOML or JSON depending on
+     *  the extension of `name`. Definitions override built in mnemonics of the same name
+     */
+    pub fn extend_isa(&mut self, name: &str, text: &str) -> Result<&mut Self, Diagnostic> {
+        let defs = IsaExtensions::parse_extensions(name, text)?;
+        self.table.extend(defs);
+        Ok(self)
+    }
+
+    /**
+     *  Assembles every source in the session. Bad lines do not stop the run: they are skipped
+     *  and every error found is returned together, after any warnings
+     */
+    pub fn assemble(&self) -> Result<AssembledProgram, Vec<Diagnostic>> {
+        let mut symbol_table = SymbolTable::new();
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut warnings: Vec<Diagnostic> = Vec::new();
+
+        for (name, value) in &self.defines {
+            if let Err(e) = symbol_table.define(name, *value) {
+                errors.push(Diagnostic::global(e));
+            }
+        }
+
+        let mut statements: Vec<Statement> = Vec::new();
+        let mut preprocessor = Preprocessor::new(&self.include_dirs, symbol_table.clone());
+        for (name, text) in &self.sources {
+            let lines = preprocessor.source(Parser::lines(text, name), &mut errors);
+            let (mut parsed, mut parse_errors) = Parser::parse_lines(&lines);
+            statements.append(&mut parsed);
+            errors.append(&mut parse_errors);
+        }
+        errors.extend(preprocessor.finish());
+
+        let mut instr_v: Vec<CodeGen::SourceLine<Instruction>> = Vec::new();
+        let mut data_v: Vec<CodeGen::SourceLine<Data>> = Vec::new();
+
+        // a segment start has to be processed before any instructions or data
+        let mut segment: Option<Sections> = None;
+        let mut code_segment_present = false;
+        let mut code_base_addr = 0;
+        let mut data_base_addr = 0;
+        // wider than an address, so a segment can end right at the top of the address space
+        let mut c_addr: u64 = 0;
+        let mut d_addr: u64 = 0;
+        let mut last_tag: Option<String> = None;
+        // tags are case-sensitive, but `Loop` next to `loop` is most likely a typo
+        let mut tags_by_case: HashMap<String, String> = HashMap::new();
+        // `.set noat` hands $at over to the programmer, pseudo-instructions may not use it then
+        let mut noat = false;
+        // under `.set reorder` the assembler fills the delay slot of every branch and jump
+        let mut reorder = false;
+
+        for statement in statements {
+            let span = statement.span;
+            match statement.kind {
+                StatementKind::Label(tag) => {
+                    let addr = match segment {
+                        Some(Sections::Code) => c_addr,
+                        Some(Sections::Data) => d_addr,
+                        None => {
+                            errors.push(Diagnostic::new(
+                                &span,
+                                SyntaxError::OutsideSegment(
+                                    format!("Tag \"{tag}\""),
+                                    ".text/.data".into(),
+                                ),
+                            ));
+                            continue;
+                        }
+                    };
+                    if let Err(e) = symbol_table.log_addr(tag.clone(), addr as u32) {
+                        errors.push(Diagnostic::new(&span, e));
+                        continue;
+                    }
+                    match tags_by_case.get(&tag.to_ascii_lowercase()) {
+                        Some(other) if *other != tag => warnings.push(Diagnostic::warning(
+                            &span,
+                            AssemblyWarning::TagCaseCollision(tag.clone(), other.clone()),
+                        )),
+                        Some(_) => {}
+                        None => {
+                            tags_by_case.insert(tag.to_ascii_lowercase(), tag.clone());
+                        }
+                    }
+                    match segment {
+                        Some(Sections::Code) => instr_v.push((span, None)),
+                        _ => data_v.push((span, None)),
+                    }
+                    last_tag = Some(tag);
+                }
+                StatementKind::Directive(name, args) if name == ".text" || name == ".data" => {
+                    let (section, has_contents) = if name == ".text" {
+                        code_segment_present = true;
+                        (Sections::Code, !instr_v.is_empty())
+                    } else {
+                        (Sections::Data, !data_v.is_empty())
+                    };
+                    match segment_address(&name, &args, &span, has_contents, &symbol_table) {
+                        Ok(Some(addr)) if name == ".text" => {
+                            code_base_addr = addr;
+                            c_addr = addr as u64;
+                        }
+                        Ok(Some(addr)) => {
+                            data_base_addr = addr;
+                            d_addr = addr as u64;
+                        }
+                        Ok(None) => {}
+                        Err(e) => errors.push(e),
+                    }
+                    segment = Some(section);
+                }
+                StatementKind::Directive(name, args)
+                    if matches!(name.as_str(), ".equ" | ".eqv")
+                        || (name == ".set" && args.len() == 2) =>
+                {
+                    if let Err(e) = define_constant(&args, &span, &mut symbol_table) {
+                        errors.push(e);
+                    }
+                }
+                StatementKind::Directive(name, args) if name == ".set" => {
+                    let option = match set_option(&args, &span) {
+                        Ok(option) => option,
+                        Err(e) => {
+                            errors.push(e);
+                            continue;
+                        }
+                    };
+                    match option.as_str() {
+                        "at" => noat = false,
+                        "noat" => noat = true,
+                        "reorder" => reorder = true,
+                        "noreorder" => reorder = false,
+                        other => errors.push(Diagnostic::new(
+                            &args[0].span,
+                            SyntaxError::UnknownDirective(format!(".set {other}")),
+                        )),
+                    }
+                }
+                StatementKind::Directive(name, _) if !is_data_directive(&name) => {
+                    errors.push(Diagnostic::new(
+                        &span.narrow(&name),
+                        SyntaxError::UnknownDirective(name),
+                    ));
+                }
+                StatementKind::Directive(name, args) => {
+                    if !matches!(segment, Some(Sections::Data)) {
+                        errors.push(Diagnostic::new(
+                            &span,
+                            SyntaxError::OutsideSegment(
+                                format!("Directive \"{name}\""),
+                                ".data".into(),
+                            ),
+                        ));
+                        continue;
+                    }
+                    let tagname = last_tag.take().unwrap_or_else(|| name.clone());
+                    let room = (1u64 << 32).saturating_sub(d_addr);
+                    match Data::new(&name, &args, tagname, &span, &symbol_table, room) {
+                        Ok(d) => {
+                            advance(&mut d_addr, d.contents.len(), &span, &mut errors);
+                            data_v.push((span, Some(d)));
+                        }
+                        Err(e) => errors.push(e),
+                    }
+                }
+                StatementKind::Instruction(mnemonic, operands) => {
+                    if !matches!(segment, Some(Sections::Code)) {
+                        errors.push(Diagnostic::new(
+                            &span,
+                            SyntaxError::OutsideSegment("Instruction".into(), ".text".into()),
+                        ));
+                        continue;
+                    }
+                    if !noat {
+                        if let Some(op) = operands.iter().find(|op| uses_at(&op.kind)) {
+                            warnings
+                                .push(Diagnostic::warning(&op.span, AssemblyWarning::AtClobbered));
+                        }
+                    }
+                    // mnemonics in the table win, so an extension can redefine a pseudo-instruction
+                    let expansion =
+                        if !self.table.contains(&mnemonic) && Pseudo::is_pseudo(&mnemonic) {
+                            if noat && Pseudo::uses_at(&mnemonic) {
+                                errors.push(Diagnostic::new(
+                                    &span,
+                                    SyntaxError::AtReserved(mnemonic.clone()),
+                                ));
+                            }
+                            match Pseudo::expand(&mnemonic, operands, &span, &symbol_table) {
+                                Ok(expansion) => expansion,
+                                Err(e) => {
+                                    errors.push(e);
+                                    advance(&mut c_addr, 4, &span, &mut errors);
+                                    instr_v.push((span, Some(Instruction::placeholder())));
+                                    continue;
+                                }
+                            }
+                        } else {
+                            vec![(mnemonic, operands)]
+                        };
+                    for (mnemonic, operands) in expansion {
+                        let instr = Instruction::new(
+                            &mnemonic,
+                            operands,
+                            &span,
+                            &mut symbol_table,
+                            &self.table,
+                            self.isa,
+                        )
+                        .unwrap_or_else(|e| {
+                            errors.push(e);
+                            Instruction::placeholder()
+                        });
+                        if let Some((nops, hazard)) = Pipeline::hazard(&instr_v, &instr) {
+                            if reorder {
+                                for _ in 0..nops {
+                                    let mut nop = Instruction::placeholder();
+                                    nop.note = Some(format!("nop inserted: {hazard}"));
+                                    advance(&mut c_addr, 4, &span, &mut errors);
+                                    instr_v.push((span.clone(), Some(nop)));
+                                }
+                            } else {
+                                warnings.push(Diagnostic::warning(&span, hazard));
+                            }
+                        }
+                        advance(&mut c_addr, 4, &span, &mut errors);
+                        if !(reorder && Pipeline::has_delay_slot(&instr)) {
+                            instr_v.push((span.clone(), Some(instr)));
+                            continue;
+                        }
+                        match Pipeline::take_slot_filler(&mut instr_v, &instr) {
+                            Some((filler_span, Some(mut filler))) => {
+                                filler.note = Some(format!(
+                                    "moved into the delay slot of line {}",
+                                    span.line
+                                ));
+                                instr_v.push((span.clone(), Some(instr)));
+                                instr_v.push((filler_span, Some(filler)));
+                            }
+                            _ => {
+                                let mut nop = Instruction::placeholder();
+                                nop.note = Some("nop inserted in the delay slot".into());
+                                advance(&mut c_addr, 4, &span, &mut errors);
+                                instr_v.push((span.clone(), Some(instr)));
+                                instr_v.push((span.clone(), Some(nop)));
+                            }
+                        }
+                    }
+                }
+                StatementKind::Invalid => {
+                    // the error was already reported by the parser
+                    if matches!(segment, Some(Sections::Code)) {
+                        advance(&mut c_addr, 4, &span, &mut errors);
+                        instr_v.push((span, Some(Instruction::placeholder())));
+                    }
+                }
+            }
+        }
+
+        if !code_segment_present {
+            errors.push(Diagnostic::global(MetadataError::NoSegmentData(
+                ".text".into(),
+            )));
+        }
+
+        //compilation
+        let mut listing: Vec<String> = Vec::new();
+        let compiled = CodeGen::compile(
+            code_base_addr,
+            instr_v,
+            data_base_addr,
+            data_v,
+            &mut symbol_table,
+            &self.table,
+            &mut listing,
+        );
+        let (code, data) = match compiled {
+            Ok(_) if !errors.is_empty() => {
+                warnings.append(&mut errors);
+                return Err(warnings);
+            }
+            Ok(cd) => cd,
+            Err(mut e) => {
+                warnings.append(&mut errors);
+                warnings.append(&mut e);
+                return Err(warnings);
+            }
+        };
+
+        let mut symbols = symbol_table.resolved();
+        symbols.remove(".text");
+        symbols.remove(".data");
+
+        Ok(AssembledProgram {
+            code_base_addr,
+            code,
+            data_base_addr,
+            data,
+            symbols,
+            entry: code_base_addr,
+            listing,
+            warnings,
+        })
+    }
+}
+
+/**
+ *  Reads the optional base address of a `.text`/`.data` directive. A segment that already
+ *  has contents cannot be moved, since segments are emitted as one contiguous block.
+ *  Expressions may refer to tags defined earlier
+ */
+fn segment_address(
+    name: &str,
+    args: &[Operand],
+    span: &Span,
+    has_contents: bool,
+    symbols: &SymbolTable,
+) -> Result<Option<u32>, Diagnostic> {
+    let addr = match args {
+        [] => return Ok(None),
+        [op @ Operand {
+            kind: OperandKind::Imm(_) | OperandKind::Label(_) | OperandKind::Expr(_),
+            span: addr_span,
+        }] => {
+            let addr = match &op.kind {
+                OperandKind::Imm(v) => *v,
+                OperandKind::Label(tag) => symbols
+                    .value(tag)
+                    .map_err(|e| Diagnostic::new(addr_span, e))?,
+                OperandKind::Expr(e) => symbols
+                    .evaluate(e)
+                    .map_err(|e| Diagnostic::new(addr_span, e))?,
+                _ => unreachable!(),
+            };
+            if !(0..=u32::MAX as i64).contains(&addr) {
+                return Err(Diagnostic::new(
+                    addr_span,
+                    SyntaxError::ValueTooLarge(addr, 4),
+                ));
+            }
+            if addr % 4 != 0 {
+                return Err(Diagnostic::new(
+                    addr_span,
+                    MetadataError::Align(name.into()),
+                ));
+            }
+            addr as u32
+        }
+        [op, ..] => {
+            return Err(Diagnostic::new(
+                &op.span,
+                SyntaxError::Expected("a single address".into()),
+            ))
+        }
+    };
+    if has_contents {
+        return Err(Diagnostic::new(
+            span,
+            MetadataError::Relocation(name.into()),
+        ));
+    }
+    Ok(Some(addr))
+}
+
+/**
+ *  Moves a segment cursor past `size` bytes. Only the first item that does not fit below
+ *  the end of the 32b address space is reported
+ */
+fn advance(addr: &mut u64, size: usize, span: &Span, errors: &mut Vec<Diagnostic>) {
+    const END: u64 = 1 << 32;
+    let next = addr.checked_add(size as u64).unwrap_or(u64::MAX);
+    if *addr <= END && next > END {
+        errors.push(Diagnostic::new(span, MetadataError::SegmentOverflow));
+    }
+    *addr = next;
+}
+
+/**
+ *  Defines the constant of a `.equ name, value` directive. The value is evaluated right
+ *  away, so it can only refer to tags and constants defined earlier
+ */
+pub fn define_constant(
+    args: &[Operand],
+    span: &Span,
+    symbols: &mut SymbolTable,
+) -> Result<(), Diagnostic> {
+    let (name, name_span, value) = match args {
+        [Operand {
+            kind: OperandKind::Label(name),
+            span: name_span,
+        }, value] => (name, name_span, value),
+        [op, _] => {
+            return Err(Diagnostic::new(
+                &op.span,
+                SyntaxError::Expected("constant name".into()),
+            ))
+        }
+        [_, _, extra, ..] => {
+            return Err(Diagnostic::new(
+                &extra.span,
+                SyntaxError::ExtraOperand(extra.span.text().into()),
+            ))
+        }
+        _ => return Err(Diagnostic::new(span, SyntaxError::MissingOperand)),
+    };
+    let v = match &value.kind {
+        OperandKind::Imm(v) => Ok(*v),
+        OperandKind::Label(tag) => symbols.value(tag),
+        OperandKind::Expr(e) => symbols.evaluate(e),
+        _ => {
+            return Err(Diagnostic::new(
+                &value.span,
+                SyntaxError::Expected("constant value".into()),
+            ))
+        }
+    }
+    .map_err(|e| Diagnostic::new(&value.span, e))?;
+    symbols
+        .define(name, v)
+        .map_err(|e| Diagnostic::new(name_span, e))
+}
+
+/**
+ *  Reads the single option of a `.set` directive
+ */
+fn set_option(args: &[Operand], span: &Span) -> Result<String, Diagnostic> {
+    match args {
+        [Operand {
+            kind: OperandKind::Label(option),
+            ..
+        }] => Ok(option.to_ascii_lowercase()),
+        [] => Err(Diagnostic::new(span, SyntaxError::MissingOperand)),
+        [op, ..] => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("a .set option".into()),
+        )),
+    }
+}
+
+/**
+ *  Whether an operand written by the programmer refers to $at
+ */
+fn uses_at(kind: &OperandKind) -> bool {
+    matches!(
+        kind,
+        OperandKind::Register(Register::AT) | OperandKind::Memory(_, Register::AT)
+    )
+}
+
+impl AssembledProgram {
+    /**
+     *  Packs the program into a RELF executable and writes it to `path`
+     */
+    pub fn write(&self, path: String) {
+        CodeGen::pack_and_write(
+            path,
+            self.entry,
+            self.code_base_addr,
+            &self.code,
+            self.data_base_addr,
+            &self.data,
+        );
+    }
+}
+
+/**
+ *  Assembles `src` and returns the line of every error, in the order they are reported.
+ *  Errors that do not belong to a line are left out
+ */
+#[cfg(test)]
+pub fn error_lines(src: &str) -> Vec<usize> {
+    let errors = Assembler::new()
+        .add_source("test.s", src)
+        .assemble()
+        .unwrap_err();
+    errors
+        .iter()
+        .filter_map(|e| e.span.as_ref().map(|s| s.line))
+        .collect()
+}
+
+#[test]
+fn assemble_in_memory() {
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x100\nmsg: .asciiz \"hi\"\n.text 0x0\nstart: addi $t0, $zero, 1\nj start",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(program.code == [0x20080001, 0x08000000]);
+    assert!(program.data == [b'h', b'i', 0]);
+    assert!(program.symbols["msg"] == 0x100);
+    assert!(program.symbols["start"] == 0x0);
+    assert!(program.entry == 0x0);
+}
+
+#[test]
+fn concurrent_assemblies_do_not_share_labels() {
+    let handles: Vec<_> = (0..8u32)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let src = format!(".text 0x{:X}\nj target\nnop\ntarget:\nj target", i * 0x100);
+                let program = Assembler::new().add_source("t.s", &src).assemble().unwrap();
+                (i, program)
+            })
+        })
+        .collect();
+
+    for h in handles {
+        let (i, program) = h.join().unwrap();
+        let target = i * 0x100 + 8;
+        assert!(program.symbols["target"] == target);
+        assert!(program.code[0] == 0x08000000 | (target >> 2));
+    }
+}
+
+#[test]
+fn all_errors_are_reported() {
+    assert!(
+        error_lines(
+            ".text 0x0\nadid $t0, $t0, 1\nbeq $t0, $zero, end\naddi $t0, $t0\nj nowhere\nend:\nnop"
+        ) == [2, 4, 5]
+    );
+
+    // an unknown directive is reported as such, wherever it appears
+    let program = Assembler::new()
+        .add_source("test.s", ".text 0x0\n.globl main\nmain: nop")
+        .assemble();
+    assert!(program.unwrap_err()[0]
+        .to_string()
+        .contains("Unknown directive \".globl\""));
+}
+
+#[test]
+fn segments_stay_inside_the_address_space() {
+    let program = Assembler::new()
+        .add_source("test.s", ".text 0xFFFFFFFC\nend: nop")
+        .assemble()
+        .unwrap();
+    assert!(program.symbols["end"] == 0xFFFFFFFC);
+
+    assert!(error_lines(".text 0xFFFFFFF8\nnop\nnop\nnop\nnop") == [4]);
+    assert!(error_lines(".data 0xFFFFFFF8\n.word 1, 2\n.byte 3\n.text 0x100\nnop") == [3]);
+    // checked before anything is allocated
+    assert!(error_lines(".data 0x100\n.space 0xFFFFFFFF\n.text 0x0\nnop") == [2]);
+}
+
+#[test]
+fn tags_share_the_listing_line_of_their_instruction() {
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x100\nmsg: .word 1\nalone:\n.byte 2\n.text 0x0\nstart: nop\nnext:\nnop",
+        )
+        .assemble()
+        .unwrap();
+    let lines: Vec<&str> = program
+        .listing
+        .iter()
+        .map(|l| l.split(" -> ").next().unwrap().trim_end())
+        .collect();
+    assert!(
+        lines
+            == [
+                "2\t| msg: .word 1",
+                "3\t| alone:",
+                "4\t| .byte 2",
+                "6\t| start: nop",
+                "7\t| next:",
+                "8\t| nop"
+            ]
+    );
+}
+
+#[test]
+fn tags_and_strings_keep_their_case() {
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x100\nmsg: .asciiz \"Hello\"\n.text 0x0\nLoop: ADDI $T0, $Zero, 1\nloop: J Loop",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(program.data == b"Hello\0");
+    assert!(program.symbols["Loop"] == 0x0);
+    assert!(program.symbols["loop"] == 0x4);
+    assert!(program.code == [0x20080001, 0x08000000]);
+
+    assert!(program.warnings.len() == 1);
+    assert!(!program.warnings[0].is_error());
+    assert!(program.warnings[0].span.as_ref().unwrap().line == 5);
+}
+
+#[test]
+fn extended_isa() {
+    let program = Assembler::new()
+        .extend_isa(
+            "core.toml",
+            "[[instruction]]\nname = \"hlt\"\nformat = \"special\"\nword = 0xFC000000\n\n[[instruction]]\nname = \"swap\"\nformat = \"r\"\nfunct = 0x3F\noperands = \"rd, rs\"",
+        )
+        .unwrap()
+        .add_source("test.s", ".text 0x0\nswap $t0, $t1\nhlt")
+        .assemble()
+        .unwrap();
+
+    assert!(program.code == [0x0120403F, 0xFC000000]);
+}
+
+#[test]
+fn at_register_checks() {
+    let src = |body: &str| format!(".text 0x0\n{body}\nend: nop");
+
+    let program = Assembler::new()
+        .add_source("test.s", &src("addu $at, $t0, $t1\nblt $t0, $t1, end"))
+        .assemble()
+        .unwrap();
+    assert!(program.warnings.len() == 1);
+    assert!(program.warnings[0].span.as_ref().unwrap().line == 2);
+
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            &src(".Set NOAT\naddu $at, $t0, $t1\n.SET At\nbge $t0, $t1, end"),
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.warnings.is_empty());
+
+    assert!(error_lines(&src(".set noat\nblt $t0, $t1, end\n.set bogus")) == [3, 4]);
+}
+
+#[test]
+fn relocation_operators() {
+    // a plain tag is its full address, which has to fit in the field
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x100\nsmall: .word 1\n.text 0x1000\nlw $t1, small($zero)\naddi $t0, $zero, small",
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.code == [0x8C090100, 0x20080100]);
+    assert!(error_lines(".data 0x8000\nbig: .word 1\n.text 0x0\nlw $t1, big($zero)") == [4]);
+
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x10008000\nval: .word 7\n.text 0x0\nlui $t0, %hi(val)\nlw $t1, %lo(val)($t0)\naddiu $a0, $a0, %lo(val)\nlui $t2, %hi(0x12348000)\nori $t3, $zero, %lo(0x12348000)\nsw $t1, %lo(val)($zero)",
+        )
+        .assemble()
+        .unwrap();
+    assert!(
+        program.code == [0x3C081001, 0x8D098000, 0x24848000, 0x3C0A1235, 0x340B8000, 0xAC098000]
+    );
+
+    // the low half of a constant fills a signed field just like that of a tag
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".text 0x0\nlw $t0, %lo(0x10008000)($t1)\naddiu $t1, $t1, %lo(-1)",
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.code == [0x8D288000, 0x2529FFFF]);
+}
+
+#[test]
+fn constant_expressions() {
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x100\ntable: .word end - start, table + 4, (1 << 4) | 0x3\nbuf: .space 2 * (8 - 6)\n.text 0x0\nstart: addi $t0, $zero, -(3 + 4) * 2\nlw $t1, 4 * 2($sp)\nori $t2, $zero, ~0 & 0xFF00\nlui $t3, %hi(table + 0x10000)\nbne $t0, $zero, start + 8\nend:",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(program.data[..12] == [0, 0, 0, 0x14, 0, 0, 1, 4, 0, 0, 0, 0x13]);
+    assert!(program.data.len() == 16);
+    assert!(program.code == [0x2008FFF2, 0x8FA90008, 0x340AFF00, 0x3C0B0001, 0x1500FFFD]);
+
+    // a folded target means the same as one that names a tag
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".text 0x0\nstart: beq $t0, $zero, 8 + 8\nbeq $t0, $zero, start + 0x10\nj 8 + 8",
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.code == [0x11000003, 0x11000002, 0x08000004]);
+
+    assert!(error_lines(".data 0x100\n.word nowhere + 1\n.space later\n.text 0x0\naddi $t0, $zero, 0xFFFFFFFF + 1\naddi $t0, $zero, 1 / 0\nlater:") == [5, 6, 3, 2]);
+}
+
+#[test]
+fn symbolic_constants() {
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".equ MMIO, 0xFFFF0000\n.eqv PRINT_INT, 1\n.set WORDS, 4\n.data 0x100\nbuf: .space WORDS * 4\n.word PRINT_INT, LATE\n.text 0x0\nli $v0, PRINT_INT\nlui $t0, %hi(MMIO)\nsll $t1, $t1, WORDS - 2\naddi $t2, $zero, LATE\naddi $t3, $zero, -WORDS\n.equ LATE, buf + 4",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(program.data[16..] == [0, 0, 0, 1, 0, 0, 1, 4]);
+    assert!(program.code == [0x24020001, 0x3C08FFFF, 0x00094880, 0x200A0104, 0x200BFFFC]);
+    assert!(!program.symbols.contains_key("MMIO"));
+
+    assert!(
+        error_lines(".equ N, 1\n.equ N, 2\n.text 0x0\nN: nop\n.set M, later\nlater: nop")
+            == [2, 4, 5]
+    );
+    assert!(error_lines(".text 0x0\nloop: nop\n.data 0x100\nloop: .word 1") == [4]);
+
+    // a numeric branch target is an address, however it is written and wherever a constant
+    // in it is defined
+    for src in [
+        ".text 0x0\nbeq $t0, $zero, 0x10",
+        ".equ Z, 0\n.text 0x0\nbeq $t0, $zero, 0x10 + Z",
+        ".equ T, 0x10\n.text 0x0\nbeq $t0, $zero, T",
+        ".text 0x0\nbeq $t0, $zero, T\n.equ T, 0x10",
+    ] {
+        let program = Assembler::new()
+            .add_source("test.s", src)
+            .assemble()
+            .unwrap();
+        assert!(program.code == [0x11000003]);
+    }
+}
@@ -1,35 +1,47 @@
 use crate::Structures::Data::{DType, Data};
-use crate::Structures::Instruction::{Instruction, InstructionType};
+use crate::Structures::Instruction::Instruction;
+use crate::Structures::InstructionTable::{ImmKind, InstructionTable};
 use crate::Structures::RELFHeaders::{RelfHeader32, SectionHeader32};
 
-use super::TagResolution;
-use super::TagResolution::Tag;
+use super::TagResolution::{Immediate, SymbolTable, Tag};
 
+use crate::Structures::Diagnostic::{Diagnostic, Span};
 use crate::Structures::Errors::CompileError;
+#[cfg(test)]
+use crate::Structures::{Data::parse_data_line, Instruction::parse_instruction};
 
 use structure::*;
 
 use std::fs::File;
 use std::io::Write;
 
+/// A source line, paired with what it was parsed into (if anything)
+pub type SourceLine<T> = (Span, Option<T>);
+
+#[allow(clippy::too_many_arguments)] // the structure! formats expand to wide pack functions
 pub fn pack_and_write(
     path: String,
+    entry: u32,
     code_base_addr: u32,
-    code: Vec<u32>,
+    code: &[u32],
     data_base_addr: u32,
-    data: Vec<u8>,
+    data: &[u8],
 ) {
-    let mut relf_header = RelfHeader32::default();
-    relf_header.e_entry = code_base_addr;
-
-    let mut program_header = SectionHeader32::default();
-    program_header.p_type = 0x00000001;
-    program_header.p_offset = (relf_header.e_phentsize * 2) as u32;
-    program_header.p_vaddr = code_base_addr;
-    program_header.p_paddr = code_base_addr;
-    program_header.p_filesz = code.len() as u32 * 4;
-    program_header.p_memsz = code.len() as u32 * 4;
-    program_header.p_flags = 0x05000000;
+    let relf_header = RelfHeader32 {
+        e_entry: entry,
+        ..Default::default()
+    };
+
+    let program_header = SectionHeader32 {
+        p_type: 0x00000001,
+        p_offset: (relf_header.e_phentsize * 2) as u32,
+        p_vaddr: code_base_addr,
+        p_paddr: code_base_addr,
+        p_filesz: code.len() as u32 * 4,
+        p_memsz: code.len() as u32 * 4,
+        p_flags: 0x05000000,
+        ..Default::default()
+    };
 
     let data_header = SectionHeader32 {
         p_type: 0x00000001,
@@ -107,216 +119,263 @@ pub fn pack_and_write(
             .expect("Could not write to file");
 
         for c in code {
-            fd.write(&c.to_be_bytes()).expect("Could not write to file");
+            fd.write_all(&c.to_be_bytes())
+                .expect("Could not write to file");
         }
 
         // data is already an u8 buffer :)
-        fd.write(&data).expect("Could not write to file");
+        fd.write_all(data).expect("Could not write to file");
     }
 }
 
 pub fn compile(
     code_base_addr: u32,
-    instr_v: Vec<((String, usize), Option<Instruction>)>,
+    instr_v: Vec<SourceLine<Instruction>>,
     data_base_addr: u32,
-    data_v: Vec<((String, usize), Option<Data>)>,
-) -> Result<(Vec<u32>, Vec<u8>), CompileError> {
+    data_v: Vec<SourceLine<Data>>,
+    symbols: &mut SymbolTable,
+    table: &InstructionTable,
+    listing: &mut Vec<String>,
+) -> Result<(Vec<u32>, Vec<u8>), Vec<Diagnostic>> {
+    // errors do not stop compilation, so every one of them can be reported at once
+    let mut errors: Vec<Diagnostic> = Vec::new();
     let mut d_addr = data_base_addr;
     let mut data: Vec<u8> = Vec::new(); // dense array of contiguous data. We do not allow hosting data at different mem locations for now
 
-    for ((original_line, line_count), data_maybe) in data_v {
-        print!("{}\t| {} ", line_count, original_line);
-        match data_maybe {
-            Some(dt) => {
-                //ensure alignment
-                match dt.dt {
-                    DType::Word => {
-                        if d_addr % 4 != 0 {
-                            return Err(CompileError::AlignmentError(4, d_addr, dt.tagname));
-                        }
-                    }
-                    DType::Half => {
-                        if d_addr % 2 != 0 {
-                            return Err(CompileError::AlignmentError(2, d_addr, dt.tagname));
-                        }
-                    }
-                    DType::Byte | DType::ZTerminatedString | DType::String | DType::Space => {}
+    let mut data_lines = data_v.into_iter().peekable();
+    while let Some((span, data_maybe)) = data_lines.next() {
+        if data_maybe.is_none()
+            && data_lines
+                .peek()
+                .is_some_and(|(next, _)| span.same_line(next))
+        {
+            continue;
+        }
+        let mut entry = format!("{}\t| {} ", span.line, span.line_text);
+        if let Some(mut dt) = data_maybe {
+            for fixup in std::mem::take(&mut dt.fixups) {
+                let patched = match symbols.evaluate(&fixup.expr) {
+                    Ok(v) => dt
+                        .patch(&fixup, v)
+                        .map_err(|e| Diagnostic::new(&fixup.span, e)),
+                    Err(e) => Err(Diagnostic::new(&fixup.span, e)),
+                };
+                if let Err(e) = patched {
+                    errors.push(e);
                 }
+            }
 
-                TagResolution::log_addr(dt.tagname, d_addr);
-
-                print!(" -> 0x");
-                let mut print_ct = 0;
-                for byte in &dt.contents {
-                    print!("{:02X}", byte);
-                    print_ct += 1;
-                    if print_ct > 4 {
-                        break;
+            //ensure alignment
+            match dt.dt {
+                DType::Word => {
+                    if !d_addr.is_multiple_of(4) {
+                        errors.push(Diagnostic::new(
+                            &span,
+                            CompileError::AlignmentError(4, d_addr, dt.tagname.clone()),
+                        ));
                     }
                 }
-                if dt.contents.len() > 4 {
-                    print!("...")
-                };
-
-                println!(" @ [0x{:08X}]", d_addr);
-                d_addr += dt.contents.len() as u32;
-                for byte in dt.contents {
-                    data.push(byte);
+                DType::Half => {
+                    if !d_addr.is_multiple_of(2) {
+                        errors.push(Diagnostic::new(
+                            &span,
+                            CompileError::AlignmentError(2, d_addr, dt.tagname.clone()),
+                        ));
+                    }
                 }
+                DType::Byte | DType::ZTerminatedString | DType::String | DType::Space => {}
             }
-            None => {
-                println!();
+
+            entry.push_str(" -> 0x");
+            for byte in dt.contents.iter().take(5) {
+                entry.push_str(&format!("{:02X}", byte));
             }
+            if dt.contents.len() > 4 {
+                entry.push_str("...")
+            };
+
+            entry.push_str(&format!(" @ [0x{:08X}]", d_addr));
+            d_addr = d_addr.wrapping_add(dt.contents.len() as u32);
+            data.extend(dt.contents);
         }
+        listing.push(entry);
     }
 
-    if code_base_addr % 2 != 0 {
-        return Err(CompileError::AlignmentError(
+    if !code_base_addr.is_multiple_of(2) {
+        errors.push(Diagnostic::global(CompileError::AlignmentError(
             2,
             code_base_addr,
             String::from(".text"),
-        ));
+        )));
     }
 
     let mut c_addr = code_base_addr;
     let mut code: Vec<u32> = Vec::new();
 
-    for ((original_line, line_count), instr_maybe) in instr_v {
-        print!("{}\t| {} ", line_count, original_line);
-        match instr_maybe {
-            Some(instr) => {
-                let compiled: u32 = compile_single(instr, c_addr)?;
-                println!(" -> 0x{:08X} @ [0x{:08X}]", compiled, c_addr);
-                c_addr += 0x4;
-                code.push(compiled);
+    let mut instr_lines = instr_v.into_iter().peekable();
+    while let Some((span, instr_maybe)) = instr_lines.next() {
+        // a tag is listed together with the instruction written after it on the same line
+        if instr_maybe.is_none()
+            && instr_lines
+                .peek()
+                .is_some_and(|(next, _)| span.same_line(next))
+        {
+            continue;
+        }
+        let mut entry = format!("{}\t| {} ", span.line, span.line_text);
+        if let Some(instr) = instr_maybe {
+            let note = instr.note.clone();
+            let compiled: u32 = match compile_single(instr, c_addr, symbols) {
+                Ok(c) => c,
+                Err(e) => {
+                    errors.push(match &e {
+                        CompileError::TagResolution(tag) => Diagnostic::new(&span.narrow(tag), e),
+                        _ => Diagnostic::new(&span, e),
+                    });
+                    // keep the slot so the following addresses do not shift
+                    0
+                }
+            };
+            entry.push_str(&format!(" -> 0x{:08X} @ [0x{:08X}]", compiled, c_addr));
+            // show what was actually encoded, which differs from the source for pseudo-instructions
+            if let Some(text) = table.disassemble(compiled, c_addr) {
+                entry.push_str(&format!("  {text}"));
             }
-            None => {
-                println!();
+            if let Some(note) = note {
+                entry.push_str(&format!("  ; {note}"));
             }
+            // running past the end of the address space was reported by the assembler pass
+            c_addr = c_addr.wrapping_add(0x4);
+            code.push(compiled);
         }
+        listing.push(entry);
     }
 
     //ensure no overlaps between segments
     //code is either after the data segment or before it
-    assert!(
-        ((code_base_addr >= data_base_addr + data.len() as u32)
-            || (code.len() as u32 * 4 + code_base_addr < data_base_addr)),
-        "Code and Data segments overlap!"
-    );
+    let (code_start, data_start) = (code_base_addr as u64, data_base_addr as u64);
+    if !((code_start >= data_start + data.len() as u64)
+        || (code_start + code.len() as u64 * 4 < data_start))
+    {
+        errors.push(Diagnostic::global(CompileError::SegmentOverlap));
+    }
 
-    Ok((code, data))
+    if errors.is_empty() {
+        Ok((code, data))
+    } else {
+        Err(errors)
+    }
 }
 
-fn compile_single(instr: Instruction, addr: u32) -> Result<u32, CompileError> {
+fn compile_single(
+    instr: Instruction,
+    addr: u32,
+    symbols: &SymbolTable,
+) -> Result<u32, CompileError> {
     //
     // See https://uweb.engr.arizona.edu/~ece369/Resources/spim/MIPSReference.pdf for sources on encoding formats
     // Not all instructions are implemented but including them now saves future time
     //
     // See https://www.eg.bucknell.edu/~csci320/mips_web/ for checking the correctness of the encoding
 
-    if instr.args.rs > 32 {
-        return Err(CompileError::RegisterParse("RS".into()));
-    }
-    if instr.args.rd > 32 {
-        return Err(CompileError::RegisterParse("RD".into()));
-    }
-    if instr.args.rt > 32 {
-        return Err(CompileError::RegisterParse("RT".into()));
-    }
+    // the definition provides the fixed bits, every operand field not in the pattern is 0
+    let args = &instr.args;
+    let rs_c = (args.rs.number() << 21) & 0b00000011111000000000000000000000;
+    let rt_c = ((args.rt.number() | args.ft.number()) << 16) & 0b00000000000111110000000000000000;
+    let rd_c = ((args.rd.number() | args.fs.number()) << 11) & 0b00000000000000001111100000000000;
+    let sham_c = ((args.sham | args.fd.number()) << 6) & 0b00000000000000000000011111000000;
+    let imm_c = match instr.def.imm {
+        ImmKind::None => 0,
+        ImmKind::Jump => jump_target(instr.args.target, addr, symbols)?,
+        // a branch target is always an address, whether it is written as a tag, a constant,
+        // an expression or a plain number
+        ImmKind::Branch => {
+            let target = instr.args.target.value(symbols)? as u32;
+            branch_offset(target, addr)? as u32 & 0b00000000000000001111111111111111
+        }
+        kind => imm_field(kind, instr.args.imm, symbols)? & 0b00000000000000001111111111111111,
+    };
 
-    Ok(match instr.itype {
-        InstructionType::I => compile_I(instr, addr)?,
-        InstructionType::R => compile_R(instr)?,
-        InstructionType::J => compile_J(instr)?,
-        InstructionType::Special => instr.func,
-    })
+    Ok(instr.def.word | rs_c | rt_c | rd_c | sham_c | imm_c)
 }
 
-fn compile_I(instr: Instruction, addr: u32) -> Result<u32, CompileError> {
-    let imm = match instr.args.imm {
-        Tag::Imm(v, imm_sign_negative) => {
-            if imm_sign_negative {
-                v | 0b00000000000000001000000000000000
-            } else {
-                v
-            }
-        }
-        Tag::Resolved(tagaddr) => {
-            if tagaddr < addr {
-                //since tagaddr and addr are u32, we need to force them to fit to 16b with the bitmask
-                ((tagaddr.overflowing_sub(addr).0 - 4) >> 2) & 0x0000ffff
-            } else {
-                (tagaddr - addr - 4) >> 2
-            }
-        }
-        Tag::BuildPending(s) => {
-            let tagaddr = TagResolution::resolve(s)?;
-            if tagaddr < addr {
-                ((tagaddr.overflowing_sub(addr).0 - 4) >> 2) & 0x0000ffff
-            } else {
-                (tagaddr - addr - 4) >> 2
-            }
+/**
+ *  Value of a 16b immediate field, signed or unsigned as `kind` says
+ */
+fn imm_field(kind: ImmKind, imm: Immediate, symbols: &SymbolTable) -> Result<u32, CompileError> {
+    let value = match imm {
+        Immediate::Value(v) => v.value(symbols)?,
+        // the halves of an address are raw bit patterns, %lo is sign extended by the cpu
+        // so %hi carries one when bit 15 is set
+        Immediate::Hi(e) => {
+            return Ok(((symbols.evaluate(&e)? as u32).wrapping_add(0x8000) >> 16) & 0xffff)
         }
+        Immediate::Lo(e) => return Ok(symbols.evaluate(&e)? as u32 & 0xffff),
     };
 
-    if imm > 65536 {
-        return Err(CompileError::ImmSize);
+    let range = match kind {
+        ImmKind::Unsigned => 0..=0xffff,
+        _ => -0x8000..=0x7fff,
     };
-    let func_c = (instr.func << 26) & 0b11111100000000000000000000000000;
-    let rs_c = (instr.args.rs << 21) & 0b00000011111000000000000000000000;
-    let rt_c = (instr.args.rt << 16) & 0b00000000000111110000000000000000;
-    let imm_c = imm & 0b00000000000000001111111111111111;
-
-    Ok(func_c | rs_c | rt_c | imm_c)
+    if !range.contains(&value) {
+        return Err(CompileError::ImmRange(value, kind == ImmKind::Unsigned));
+    }
+    Ok(value as u32)
 }
 
-fn compile_R(instr: Instruction) -> Result<u32, CompileError> {
-    let rs_c = (instr.args.rs << 21) & 0b00000011111000000000000000000000;
-    let rt_c = (instr.args.rt << 16) & 0b00000000000111110000000000000000;
-    let rd_c = (instr.args.rd << 11) & 0b00000000000000001111100000000000;
-    let sham_c = (instr.args.sham << 6) & 0b00000000000000000000011111000000;
-    let func_c = instr.func & 0b00000000000000000000000000111111;
-
-    Ok(rs_c | rt_c | rd_c | sham_c | func_c)
+/**
+ *  Word offset from the instruction following `addr` to `tagaddr`, as encoded in branches
+ */
+fn branch_offset(tagaddr: u32, addr: u32) -> Result<i64, CompileError> {
+    let offset = (tagaddr as i64 - addr as i64 - 4) >> 2;
+    if !(-0x8000..=0x7fff).contains(&offset) {
+        return Err(CompileError::BranchRange(tagaddr, addr));
+    }
+    Ok(offset)
 }
 
-fn compile_J(instr: Instruction) -> Result<u32, CompileError> {
-    let jtarg = match instr.args.imm {
-        Tag::Imm(v, imm_sign_negative) => {
-            if imm_sign_negative {
-                v | 0b00000000000000001000000000000000
-            } else {
-                v
-            }
-        }
-        Tag::Resolved(v) => v,
-        Tag::BuildPending(s) => TagResolution::resolve(s)?,
-    };
+/**
+ *  Target field of a jump at `addr`. The upper 4 bits of the address come from the delay
+ *  slot, so the target has to be a word in the same 256MB region
+ */
+fn jump_target(target: Tag, addr: u32, symbols: &SymbolTable) -> Result<u32, CompileError> {
+    let jtarg = target.value(symbols)? as u32;
 
-    let func_c = (instr.func << 26) & 0xfc000000;
-    let jtarg_c = (jtarg & !0xfc000000) >> 2;
-    Ok(func_c | jtarg_c)
+    if jtarg & 0b11 != 0 {
+        return Err(CompileError::JumpAlignment(jtarg));
+    }
+    if jtarg & 0xf0000000 != addr.wrapping_add(4) & 0xf0000000 {
+        return Err(CompileError::JumpRegion(jtarg, addr));
+    }
+    Ok((jtarg & !0xf0000000) >> 2)
 }
 
 #[test]
 fn compile_label_data_resolution() {
-    TagResolution::init();
+    let mut symbols = SymbolTable::new();
     let d = String::from("some_tag: .word 1,2,3,4");
     let i = String::from("j some_tag");
     dbg!(&d);
     dbg!(&i);
+    let d_span = Span::of_line("test.s", 1, &d);
+    let i_span = Span::of_line("test.s", 1, &i);
 
     const ADDR_CODE: u32 = 0x00000000;
     const ADDR_DATA: u32 = 0x0000ff00;
-    let (code, data) = match compile(
+    // data tags are logged by the assembler pass before compilation
+    symbols.log_addr("some_tag".into(), ADDR_DATA).unwrap();
+    let (code, _data) = match compile(
         ADDR_CODE,
-        vec![((i.clone(), 1), Some(i.into()))],
+        vec![(i_span, parse_instruction(&i).ok())],
         ADDR_DATA,
-        vec![((d.clone(), 1), Some(d.into()))],
+        vec![(d_span, Some(parse_data_line(&d)))],
+        &mut symbols,
+        &InstructionTable::new(),
+        &mut Vec::new(),
     ) {
         Ok((c, d)) => (c, d),
-        Err(eobj) => {
-            panic!("{}", eobj)
+        Err(eobjs) => {
+            panic!("{}", eobjs[0])
         }
     };
     println!("{:08X}", (code[0] & !0xfc000000) << 2);
@@ -326,23 +385,264 @@ fn compile_label_data_resolution() {
 #[test]
 #[should_panic]
 fn label_data_non_aligned() {
-    TagResolution::init();
+    let mut symbols = SymbolTable::new();
     let d = String::from("some_tag: .word 1,2,3,4");
     let i = String::from("j some_tag");
     dbg!(&d);
     dbg!(&i);
+    let d_span = Span::of_line("test.s", 1, &d);
+    let i_span = Span::of_line("test.s", 1, &i);
 
     const ADDR_CODE: u32 = 0x00000000;
     const ADDR_DATA: u32 = 0x0000ffdd;
-    let (code, data) = match compile(
+    symbols.log_addr("some_tag".into(), ADDR_DATA).unwrap();
+    if let Err(eobj) = compile(
         ADDR_CODE,
-        vec![((i.clone(), 1), Some(i.into()))],
+        vec![(i_span, parse_instruction(&i).ok())],
         ADDR_DATA,
-        vec![((d.clone(), 1), Some(d.into()))],
+        vec![(d_span, Some(parse_data_line(&d)))],
+        &mut symbols,
+        &InstructionTable::new(),
+        &mut Vec::new(),
     ) {
-        Ok((c, d)) => (c, d),
-        Err(eobj) => {
-            panic!("{}", eobj)
-        }
+        panic!("{}", eobj[0])
+    }
+}
+
+#[test]
+fn negative_immediates_and_backward_branches() {
+    let mut symbols = SymbolTable::new();
+    symbols.log_addr("top".into(), 0x0).unwrap();
+    let lines = [
+        "addi $t0, $t0, -1",
+        "bne $t0, $zero, top",
+        "addi $t0, $t0, 70000",
+        "ori $t0, $t0, 0xffff",
+        "ori $t0, $t0, -1",
+        "addi $t0, $t0, 0xffff",
+    ];
+    let instr_v = lines
+        .iter()
+        .map(|l| (Span::of_line("test.s", 1, l), parse_instruction(l).ok()))
+        .collect();
+
+    let errors = compile(
+        0x0,
+        instr_v,
+        0x1000,
+        Vec::new(),
+        &mut symbols,
+        &InstructionTable::new(),
+        &mut Vec::new(),
+    )
+    .unwrap_err();
+    // 70000 does not fit a 16b immediate, and ori/addi only take unsigned/signed values
+    assert!(errors.len() == 3);
+    assert!(errors[0].message.contains("16b"));
+    assert!(errors[1].message.contains("unsigned") && errors[1].span.as_ref().unwrap().line == 1);
+    assert!(errors[2].message.contains("signed"));
+}
+
+#[test]
+fn r_type_reference_encodings() {
+    let cases = [
+        ("add $t0, $t1, $t2", 0x012A4020),
+        ("addu $v0, $a0, $a1", 0x00851021),
+        ("sub $s0, $s1, $s2", 0x02328022),
+        ("subu $t0, $t1, $t2", 0x012A4023),
+        ("and $t0, $t1, $t2", 0x012A4024),
+        ("or $t0, $t1, $t2", 0x012A4025),
+        ("xor $t0, $t1, $t2", 0x012A4026),
+        ("nor $t0, $t1, $t2", 0x012A4027),
+        ("slt $t0, $t1, $t2", 0x012A402A),
+        ("sltu $t0, $t1, $t2", 0x012A402B),
+        ("sll $t0, $t1, 4", 0x00094100),
+        ("srl $t0, $t1, 4", 0x00094102),
+        ("sra $t0, $t1, 4", 0x00094103),
+        ("sllv $t0, $t1, $t2", 0x01494004),
+        ("srlv $t0, $t1, $t2", 0x01494006),
+        ("srav $t0, $t1, $t2", 0x01494007),
+        ("jr $ra", 0x03E00008),
+        ("jalr $t9", 0x0320F809),
+        ("jalr $t0, $t9", 0x03204009),
+        ("mfhi $t0", 0x00004010),
+        ("mthi $t0", 0x01000011),
+        ("mflo $t0", 0x00004012),
+        ("mtlo $t0", 0x01000013),
+        ("mult $t0, $t1", 0x01090018),
+        ("multu $t0, $t1", 0x01090019),
+        ("div $t0, $t1", 0x0109001A),
+        ("divu $t0, $t1", 0x0109001B),
+        ("syscall", 0x0000000C),
+        ("break", 0x0000000D),
+        ("nop", 0x00000000),
+    ];
+
+    for (line, word) in cases {
+        let encoded = compile_single(parse_instruction(line).unwrap(), 0x0, &SymbolTable::new());
+        assert!(encoded.unwrap() == word, "{line}");
+    }
+
+    assert!(parse_instruction("mfhi $t0, $t1").is_err());
+    assert!(parse_instruction("sll $t0, $t1, 32").is_err());
+}
+
+#[test]
+fn regimm_branches() {
+    let mut symbols = SymbolTable::new();
+    symbols.log_addr("top".into(), 0x0).unwrap();
+    symbols.log_addr("ahead".into(), 0x10).unwrap();
+
+    let cases = [
+        ("bltz $t0, ahead", 0x0, 0x05000003),
+        ("bgez $t0, ahead", 0x0, 0x05010003),
+        ("bltzal $t0, ahead", 0x0, 0x05100003),
+        ("bgezal $t0, ahead", 0x0, 0x05110003),
+        ("bgez $a0, top", 0x8, 0x0481FFFD),
+    ];
+    for (line, addr, word) in cases {
+        let instr = parse_instruction(line).unwrap();
+        assert!(
+            compile_single(instr, addr, &symbols).unwrap() == word,
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn jump_targets() {
+    let jump = |line: &str, addr: u32| {
+        compile_single(parse_instruction(line).unwrap(), addr, &SymbolTable::new())
     };
+
+    assert!(jump("j 0x10", 0x0).unwrap() == 0x08000004);
+    assert!(jump("jal 0x0FFFFFFC", 0x0).unwrap() == 0x0FFFFFFF);
+    // the region is that of the delay slot
+    assert!(jump("j 0x10000000", 0x0FFFFFFC).unwrap() == 0x08000000);
+    assert!(matches!(
+        jump("j 0x40000000", 0x0),
+        Err(CompileError::JumpRegion(0x40000000, 0x0))
+    ));
+    assert!(matches!(
+        jump("j 0x13", 0x0),
+        Err(CompileError::JumpAlignment(0x13))
+    ));
+}
+
+#[test]
+fn lui_and_unaligned_accesses() {
+    let cases = [
+        ("lui $t0, 0x1234", 0x3C081234),
+        ("lui $t0, 0xffff", 0x3C08FFFF),
+        ("lwl $t0, 3($a0)", 0x88880003),
+        ("lwr $t0, 0($a0)", 0x98880000),
+        ("swl $t0, 3($a0)", 0xA8880003),
+        ("swr $t0, ($a0)", 0xB8880000),
+    ];
+
+    for (line, word) in cases {
+        let encoded = compile_single(parse_instruction(line).unwrap(), 0x0, &SymbolTable::new());
+        assert!(encoded.unwrap() == word, "{line}");
+    }
+
+    assert!(parse_instruction("lui $t0, $t1, 4").is_err());
+}
+
+#[test]
+fn cop0_instructions() {
+    let cases = [
+        ("mfc0 $k0, $cause", 0x401A6800),
+        ("mfc0 $k1, $EPC", 0x401B7000),
+        ("mfc0 $t0, $12", 0x40086000),
+        ("mtc0 $t0, $status", 0x40886000),
+        ("mtc0 $zero, $badvaddr", 0x40804000),
+        ("tlbr", 0x42000001),
+        ("tlbwi", 0x42000002),
+        ("tlbwr", 0x42000006),
+        ("tlbp", 0x42000008),
+        ("rfe", 0x42000010),
+    ];
+
+    for (line, word) in cases {
+        let encoded = compile_single(parse_instruction(line).unwrap(), 0x0, &SymbolTable::new());
+        assert!(encoded.unwrap() == word, "{line}");
+    }
+
+    assert!(parse_instruction("mfc0 $status, $t0").is_err());
+    assert!(parse_instruction("add $t0, $status, $t1").is_err());
+}
+
+#[test]
+fn fpu_instructions() {
+    let mut symbols = SymbolTable::new();
+    symbols.log_addr("ahead".into(), 0x10).unwrap();
+
+    let cases = [
+        ("add.s $f0, $f2, $f4", 0x46041000),
+        ("sub.d $f0, $f2, $f4", 0x46241001),
+        ("mul.d $f2, $f4, $f6", 0x46262082),
+        ("div.s $f0, $f2, $f4", 0x46041003),
+        ("abs.s $f0, $f2", 0x46001005),
+        ("mov.d $f0, $f2", 0x46201006),
+        ("neg.s $f4, $f6", 0x46003107),
+        ("cvt.s.w $f0, $f2", 0x46801020),
+        ("cvt.d.s $f0, $f2", 0x46001021),
+        ("cvt.w.d $f0, $f2", 0x46201024),
+        ("c.eq.s $f2, $f4", 0x46041032),
+        ("c.lt.d $f2, $f4", 0x4624103C),
+        ("cvt.s.d $f1, $f2", 0x46201060),
+        ("cvt.d.w $f2, $f3", 0x468018A1),
+        ("mfc1 $t0, $f2", 0x44081000),
+        ("mtc1 $t0, $f2", 0x44881000),
+        ("lwc1 $f0, 4($sp)", 0xC7A00004),
+        ("swc1 $f2, 8($sp)", 0xE7A20008),
+        ("bc1t ahead", 0x45010003),
+        ("bc1f ahead", 0x45000003),
+    ];
+
+    for (line, word) in cases {
+        let encoded = compile_single(parse_instruction(line).unwrap(), 0x0, &symbols);
+        assert!(encoded.unwrap() == word, "{line}");
+    }
+
+    for bad in [
+        "add.w $f0, $f2, $f4",
+        "cvt.s.s $f0, $f2",
+        "add.s $t0, $f2, $f4",
+        "mfc1 $t0, $status",
+        "add.d $f1, $f3, $f5",
+        "mov.d $f0, $f3",
+        "cvt.d.s $f1, $f2",
+        "c.eq.d $f2, $f5",
+    ] {
+        assert!(parse_instruction(bad).is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn mips2_and_mips32_extensions() {
+    let mut symbols = SymbolTable::new();
+    symbols.log_addr("ahead".into(), 0x10).unwrap();
+
+    let cases = [
+        ("beql $t0, $t1, ahead", 0x51090003),
+        ("bnel $t0, $t1, ahead", 0x55090003),
+        ("ll $t0, 0($a0)", 0xC0880000),
+        ("sc $t0, 4($a0)", 0xE0880004),
+        ("teq $t0, $t1", 0x01090034),
+        ("tne $t0, $t1", 0x01090036),
+        ("sync", 0x0000000F),
+        ("mul $t0, $t1, $t2", 0x712A4002),
+        ("madd $t0, $t1", 0x71090000),
+        ("msub $t0, $t1", 0x71090004),
+        ("clz $t0, $t1", 0x71284020),
+        ("clo $t0, $t1", 0x71284021),
+        ("movz $t0, $t1, $t2", 0x012A400A),
+        ("movn $t0, $t1, $t2", 0x012A400B),
+    ];
+
+    for (line, word) in cases {
+        let encoded = compile_single(parse_instruction(line).unwrap(), 0x0, &symbols);
+        assert!(encoded.unwrap() == word, "{line}");
+    }
 }
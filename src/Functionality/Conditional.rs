@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+
+use super::Assembler::define_constant;
+use super::Macros::{head, tags_before};
+use super::Parser;
+use super::TagResolution::SymbolTable;
+
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::{ConditionalError, SyntaxError};
+use crate::Structures::Statement::{Operand, OperandKind, StatementKind};
+
+#[derive(Debug)]
+struct Block {
+    directive: String,
+    span: Span,
+    active: bool,
+    // once a branch has been assembled, or the enclosing block is skipped, .else is skipped too
+    taken: bool,
+    has_else: bool,
+}
+
+/**
+ *  Tracks `.if`/`.ifdef`/`.ifndef`/`.else`/`.endif` blocks on whole lines, before they are
+ *  included, expanded or parsed, so nothing in a skipped block is looked at. Conditions are
+ *  evaluated when they are reached, so they can only use predefined symbols, constants
+ *  defined earlier and, for `.ifdef`/`.ifndef`, tags defined earlier. Tag addresses are not
+ *  known yet at this point
+ */
+#[derive(Debug)]
+pub struct Conditionals {
+    blocks: Vec<Block>,
+    symbols: SymbolTable,
+    // tags, and constants whose value needs a tag address
+    names: HashSet<String>,
+}
+
+pub fn is_conditional(directive: &str) -> bool {
+    matches!(directive, ".if" | ".ifdef" | ".ifndef" | ".else" | ".endif")
+}
+
+impl Conditionals {
+    /**
+     *  Starts with the constants already in `symbols`, usually the predefined ones
+     */
+    pub fn new(symbols: SymbolTable) -> Self {
+        Conditionals {
+            blocks: Vec::new(),
+            symbols,
+            names: HashSet::new(),
+        }
+    }
+
+    /**
+     *  Whether the lines at this point are assembled
+     */
+    pub fn active(&self) -> bool {
+        self.blocks.last().is_none_or(|b| b.active)
+    }
+
+    /**
+     *  Passes `line` on if it is assembled. Conditional directives are handled here and
+     *  only any tags in front of them are passed on
+     */
+    pub fn filter(&mut self, line: Span, errors: &mut Vec<Diagnostic>) -> Option<Span> {
+        let (col, name) = match head(&line.line_text) {
+            Some((col, word)) if is_conditional(&word.to_ascii_lowercase()) => {
+                (col, word.to_ascii_lowercase())
+            }
+            _ if self.active() => {
+                self.learn(&line);
+                return Some(line);
+            }
+            _ => return None,
+        };
+        let tags = tags_before(&line, col).filter(|_| self.active());
+        if let Some(tags) = &tags {
+            self.learn(tags);
+        }
+
+        let mut statements = Vec::new();
+        let args = Parser::parse_line(&line, &mut statements).map(|_| {
+            match statements.pop().map(|s| s.kind) {
+                Some(StatementKind::Directive(_, args)) => args,
+                _ => Vec::new(),
+            }
+        });
+        let span = Span {
+            col_start: col + 1,
+            ..line.clone()
+        };
+        if let Err(e) = self.directive(&name, args, &span) {
+            errors.push(e);
+        }
+        tags
+    }
+
+    /**
+     *  Records the tags and constants defined on an assembled line. Anything wrong with
+     *  the line is reported once it is parsed again to be assembled
+     */
+    fn learn(&mut self, line: &Span) {
+        let defines = head(&line.line_text).is_some_and(|(_, word)| {
+            matches!(word.to_ascii_lowercase().as_str(), ".equ" | ".eqv" | ".set")
+        });
+        if !defines && !line.line_text.contains(':') {
+            return;
+        }
+        let mut statements = Vec::new();
+        let _ = Parser::parse_line(line, &mut statements);
+        for statement in statements {
+            match statement.kind {
+                StatementKind::Label(tag) => {
+                    self.names.insert(tag);
+                }
+                StatementKind::Directive(name, args)
+                    if matches!(name.as_str(), ".equ" | ".eqv")
+                        || (name == ".set" && args.len() == 2) =>
+                {
+                    let defined = define_constant(&args, &statement.span, &mut self.symbols);
+                    // the value may need a tag address, the name is defined all the same
+                    if let (Err(_), Some(OperandKind::Label(name))) =
+                        (defined, args.first().map(|a| &a.kind))
+                    {
+                        self.names.insert(name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /**
+     *  Handles a conditional directive, `args` being its parsed operands. A condition that
+     *  cannot be evaluated is reported and taken as false
+     */
+    fn directive(
+        &mut self,
+        name: &str,
+        args: Result<Vec<Operand>, Diagnostic>,
+        span: &Span,
+    ) -> Result<(), Diagnostic> {
+        if let (".else" | ".endif", Ok([extra, ..])) = (name, args.as_deref()) {
+            return Err(Diagnostic::new(
+                &extra.span,
+                SyntaxError::ExtraOperand(extra.span.text().into()),
+            ));
+        }
+        match name {
+            ".else" => {
+                let block = self.blocks.last_mut().ok_or_else(|| {
+                    Diagnostic::new(span, ConditionalError::Unmatched(name.into()))
+                })?;
+                if block.has_else {
+                    return Err(Diagnostic::new(span, ConditionalError::DuplicateElse));
+                }
+                block.has_else = true;
+                block.active = !block.taken;
+                block.taken = true;
+            }
+            ".endif" => {
+                self.blocks.pop().ok_or_else(|| {
+                    Diagnostic::new(span, ConditionalError::Unmatched(name.into()))
+                })?;
+            }
+            _ => {
+                let enclosing = self.active();
+                // conditions inside a skipped block are not evaluated
+                let condition = if enclosing {
+                    args.and_then(|args| condition(name, &args, span, &self.symbols, &self.names))
+                } else {
+                    Ok(false)
+                };
+                let active = matches!(condition, Ok(true));
+                self.blocks.push(Block {
+                    directive: name.into(),
+                    span: span.clone(),
+                    active,
+                    taken: active || !enclosing,
+                    has_else: false,
+                });
+                condition?;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     *  Reports every block still open at the end of the sources
+     */
+    pub fn finish(self) -> Vec<Diagnostic> {
+        self.blocks
+            .into_iter()
+            .map(|b| Diagnostic::new(&b.span, ConditionalError::Unterminated(b.directive)))
+            .collect()
+    }
+}
+
+fn condition(
+    name: &str,
+    args: &[Operand],
+    span: &Span,
+    symbols: &SymbolTable,
+    names: &HashSet<String>,
+) -> Result<bool, Diagnostic> {
+    let arg = match args {
+        [arg] => arg,
+        [] => return Err(Diagnostic::new(span, SyntaxError::MissingOperand)),
+        [_, extra, ..] => {
+            return Err(Diagnostic::new(
+                &extra.span,
+                SyntaxError::ExtraOperand(extra.span.text().into()),
+            ))
+        }
+    };
+    match (name, &arg.kind) {
+        (".ifdef" | ".ifndef", OperandKind::Label(symbol)) => {
+            Ok((symbols.is_defined(symbol) || names.contains(symbol)) == (name == ".ifdef"))
+        }
+        (".ifdef" | ".ifndef", _) => Err(Diagnostic::new(
+            &arg.span,
+            SyntaxError::Expected("symbol name".into()),
+        )),
+        (_, OperandKind::Imm(v)) => Ok(*v != 0),
+        (_, OperandKind::Label(symbol)) => symbols
+            .value(symbol)
+            .map(|v| v != 0)
+            .map_err(|e| Diagnostic::new(&arg.span, e)),
+        (_, OperandKind::Expr(e)) => symbols
+            .evaluate(e)
+            .map(|v| v != 0)
+            .map_err(|e| Diagnostic::new(&arg.span, e)),
+        _ => Err(Diagnostic::new(
+            &arg.span,
+            SyntaxError::Expected("constant expression".into()),
+        )),
+    }
+}
+
+#[test]
+fn conditional_assembly() {
+    use crate::Functionality::Assembler::{error_lines, Assembler};
+
+    let src = ".text 0x0\n.ifdef SIM\nli $v0, 10\nsyscall\n.else\nbreak\n.endif\n.if MEM_KB * 1024 > 0x1000\n.ifndef SIM\nlui $sp, MEM_KB\n.endif\n.else\naddi $sp, $zero, 0x1000\n.endif\n.if 0\nundefined_op $t0\n.if missing\n.endif\n.endif";
+    let build = |defines: &[(&str, i64)]| {
+        let mut assembler = Assembler::new();
+        for (name, value) in defines {
+            assembler.define(name, *value);
+        }
+        assembler.add_source("test.s", src).assemble()
+    };
+
+    let sim = build(&[("SIM", 1), ("MEM_KB", 4)]).unwrap();
+    assert!(sim.code == [0x2402000A, 0x0000000C, 0x201D1000]);
+    let hw = build(&[("MEM_KB", 64)]).unwrap();
+    assert!(hw.code == [0x0000000D, 0x3C1D0040]);
+
+    assert!(
+        error_lines(
+            ".text 0x0\n.endif\n.if nowhere\nnop\n.else\n.else\n.endif\n.ifdef 1\n.endif\n.if 1"
+        ) == [2, 3, 6, 8, 10]
+    );
+
+    // skipped blocks are neither included, expanded nor parsed
+    let skipped = Assembler::new()
+        .add_source(
+            "test.s",
+            ".macro inc reg\naddi \\reg, \\reg, 1\n.endm\n.text 0x0\n.ifdef SIM\n.include \"sim.s\"\n.endif\n.if 0\ninc\naddi $t0,, (\n.endif\nstart: inc $t0\n.ifdef start\nnop\n.endif",
+        )
+        .assemble()
+        .unwrap();
+    assert!(skipped.code == [0x21080001, 0x00000000]);
+
+    // an include guard keeps a header from being assembled twice
+    let dir = std::env::temp_dir().join(format!("mips_guards_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("header.s"),
+        ".ifndef HEADER_S\n.equ HEADER_S, 1\n.macro exit\nli $v0, 10\nsyscall\n.endm\n.endif\n",
+    )
+    .unwrap();
+    let main = dir.join("main.s");
+    let guarded = Assembler::new()
+        .add_source(
+            main.to_str().unwrap(),
+            ".include \"header.s\"\n.include \"header.s\"\n.text 0x0\nexit",
+        )
+        .assemble();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(guarded.unwrap().code == [0x2402000A, 0x0000000C]);
+}
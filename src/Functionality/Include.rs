@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Macros::strip_comment;
+use super::Parser;
+
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::{IncludeError, SyntaxError};
+
+/**
+ *  Finds and reads the file named by the `.include` on `line`, which starts at `col`. A file
+ *  is looked up next to the file that includes it first, then in each of `dirs` in order.
+ *  Its lines keep the name of their own file, so diagnostics point into it. `stack` holds
+ *  the files currently being included, any of them showing up again is a cycle
+ */
+pub fn read(
+    line: &Span,
+    col: usize,
+    dirs: &[PathBuf],
+    stack: &[PathBuf],
+) -> Result<(PathBuf, Vec<Span>), Diagnostic> {
+    let arg = strip_comment(&line.line_text[col + ".include".len()..]).trim();
+    let name = match arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+        Some(name) if !name.is_empty() => name,
+        _ => {
+            return Err(Diagnostic::new(
+                &line.narrow(".include"),
+                SyntaxError::Expected("file name in quotes".into()),
+            ))
+        }
+    };
+    let span = line.narrow(arg);
+
+    let here = Path::new(&line.file)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let path = std::iter::once(here)
+        .chain(dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+        .ok_or_else(|| Diagnostic::new(&span, IncludeError::NotFound(name.into())))?;
+
+    let id = identity(&path);
+    if stack.contains(&id) {
+        return Err(Diagnostic::new(&span, IncludeError::Cycle(name.into())));
+    }
+    let text = fs::read_to_string(&path).map_err(|e| {
+        Diagnostic::new(&span, IncludeError::Unreadable(name.into(), e.to_string()))
+    })?;
+    Ok((id, Parser::lines(&text, &path.to_string_lossy())))
+}
+
+/**
+ *  The same file reached through different paths has to be recognised in a cycle.
+ *  Sources that only exist in memory are known by their name
+ */
+pub fn identity(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[test]
+fn includes() {
+    use crate::Functionality::Assembler::Assembler;
+
+    let dir = std::env::temp_dir().join(format!("mips_includes_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    let write = |name: &str, text: &str| std::fs::write(dir.join(name), text).unwrap();
+    write(
+        "lib/consts.s",
+        ".equ PRINT_INT, 1\n.include \"runtime.s\"\n",
+    );
+    write("lib/runtime.s", ".macro exit\nli $v0, 10\nsyscall\n.endm\n");
+    write("src/util.s", "done: exit\n");
+    write("src/loop_a.s", ".include \"loop_b.s\"\n");
+    write("src/loop_b.s", "nop\n.include \"loop_a.s\"\n");
+    write("src/bad.s", "\n\naddi $t0, $t9\n");
+
+    let main = dir.join("src/main.s");
+    let main = main.to_str().unwrap();
+    let lib = dir.join("lib");
+    let program = Assembler::new()
+        .include_dir(lib.to_str().unwrap())
+        .add_source(
+            main,
+            ".include \"consts.s\"\n.text 0x0\nli $v0, PRINT_INT\nj done\n.include \"util.s\"",
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.code == [0x24020001, 0x08000002, 0x2402000A, 0x0000000C]);
+    assert!(program.symbols["done"] == 0x8);
+
+    let errors = Assembler::new()
+        .add_source(
+            main,
+            ".text 0x0\n.include \"bad.s\"\n.include \"loop_a.s\"\n.include \"missing.s\"\n.include missing.s",
+        )
+        .assemble()
+        .unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let at: Vec<(String, usize)> = errors
+        .iter()
+        .map(|e| {
+            let span = e.span.as_ref().unwrap();
+            let file = std::path::Path::new(&span.file).file_name().unwrap();
+            (file.to_string_lossy().into_owned(), span.line)
+        })
+        .collect();
+    assert!(
+        at == [
+            ("loop_b.s".into(), 2),
+            ("main.s".into(), 4),
+            ("main.s".into(), 5),
+            ("bad.s".into(), 3)
+        ]
+    );
+}
@@ -0,0 +1,207 @@
+use serde::Deserialize;
+
+use crate::Structures::Diagnostic::Diagnostic;
+use crate::Structures::Errors::ConfigError;
+use crate::Structures::Instruction::IsaLevel;
+use crate::Structures::InstructionTable::{ImmKind, InstrDef};
+
+/**
+ *  An extension file: a list of `[[instruction]]` tables in TOML, or an
+ *  `{"instruction": [...]}` object in JSON
+ */
+#[derive(Deserialize)]
+struct ExtensionFile {
+    #[serde(default)]
+    instruction: Vec<ExtensionDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtensionDef {
+    name: String,
+    format: String,
+    #[serde(default)]
+    operands: String,
+    imm: Option<String>,
+    isa: Option<String>,
+    word: Option<u32>,
+    opcode: Option<u32>,
+    rs: Option<u32>,
+    rt: Option<u32>,
+    rd: Option<u32>,
+    sa: Option<u32>,
+    funct: Option<u32>,
+}
+
+/**
+ *  Reads instruction definitions from an extension file. `name` picks the format:
+ *  files ending in `.json` are read as JSON, anything else as TOML
+ */
+pub fn parse_extensions(name: &str, text: &str) -> Result<Vec<InstrDef>, Diagnostic> {
+    let file: ExtensionFile = if name.to_ascii_lowercase().ends_with(".json") {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+    .map_err(|e| Diagnostic::global(ConfigError::Parse(name.into(), e)))?;
+
+    file.instruction
+        .into_iter()
+        .map(|d| d.into_def().map_err(Diagnostic::global))
+        .collect()
+}
+
+impl ExtensionDef {
+    fn into_def(self) -> Result<InstrDef, ConfigError> {
+        let name = self.name.to_ascii_lowercase();
+
+        // the format gives the default opcode, the remaining fields are placed on top of it
+        let default_opcode = match self.format.to_ascii_lowercase().as_str() {
+            "r" | "special" => Some(0x00),
+            "regimm" => Some(0x01),
+            "cop0" => Some(0x10),
+            "cop1" => Some(0x11),
+            "special2" => Some(0x1C),
+            "i" | "j" => None,
+            other => return Err(ConfigError::UnknownFormat(name, other.into())),
+        };
+
+        // an explicit word is the whole encoding, fields are only used to build one
+        let word = match self.word {
+            Some(word) => {
+                let fields = [self.opcode, self.rs, self.rt, self.rd, self.sa, self.funct];
+                if fields.iter().any(Option::is_some) {
+                    return Err(ConfigError::Invalid(
+                        name,
+                        "\"word\" cannot be combined with opcode or field values".into(),
+                    ));
+                }
+                word
+            }
+            None => self.encode(&name, default_opcode)?,
+        };
+
+        let isa = match self.isa {
+            Some(isa) => isa
+                .parse::<IsaLevel>()
+                .map_err(|e| ConfigError::Invalid(name.clone(), e))?,
+            None => IsaLevel::Mips1,
+        };
+        let imm = match self.imm.as_deref() {
+            None => default_imm(&self.operands),
+            Some("none") => ImmKind::None,
+            Some("signed") => ImmKind::Signed,
+            Some("unsigned") => ImmKind::Unsigned,
+            Some("branch") => ImmKind::Branch,
+            Some("jump") => ImmKind::Jump,
+            Some(other) => {
+                return Err(ConfigError::Invalid(
+                    name,
+                    format!("Unknown immediate kind \"{other}\""),
+                ))
+            }
+        };
+
+        let def = InstrDef::new(&name, word, &self.operands, imm, isa)
+            .map_err(|e| ConfigError::Invalid(name.clone(), e))?;
+        if def.word & !def.mask() != 0 {
+            return Err(ConfigError::Invalid(
+                name,
+                "A fixed field overlaps one of the operands".into(),
+            ));
+        }
+        Ok(def)
+    }
+
+    /**
+     *  Builds the fixed bits of the encoding from the opcode and field values
+     */
+    fn encode(&self, name: &str, default_opcode: Option<u32>) -> Result<u32, ConfigError> {
+        let opcode = self
+            .opcode
+            .or(default_opcode)
+            .ok_or_else(|| ConfigError::MissingField(name.into(), "opcode".into()))?;
+        let mut word = 0;
+        for (field, value, width, shift) in [
+            ("opcode", Some(opcode), 6, 26),
+            ("rs", self.rs, 5, 21),
+            ("rt", self.rt, 5, 16),
+            ("rd", self.rd, 5, 11),
+            ("sa", self.sa, 5, 6),
+            ("funct", self.funct, 6, 0),
+        ] {
+            if let Some(v) = value {
+                if v >= 1 << width {
+                    return Err(ConfigError::FieldWidth(name.into(), field.into(), v, width));
+                }
+                word |= v << shift;
+            }
+        }
+        Ok(word)
+    }
+}
+
+/**
+ *  Immediate kind implied by an operand pattern
+ */
+fn default_imm(pattern: &str) -> ImmKind {
+    if pattern.contains("label") {
+        ImmKind::Branch
+    } else if pattern.contains("target") {
+        ImmKind::Jump
+    } else if pattern.contains("imm") || pattern.contains("offset(base)") {
+        ImmKind::Signed
+    } else {
+        ImmKind::None
+    }
+}
+
+#[test]
+fn toml_and_json_extensions() {
+    let defs = parse_extensions(
+        "core.toml",
+        r#"
+        [[instruction]]
+        name = "hlt"
+        format = "special"
+        word = 0xFC000000
+
+        [[instruction]]
+        name = "mac"
+        format = "special2"
+        funct = 0x3F
+        operands = "rd, rs, rt"
+        isa = "mips32"
+        "#,
+    )
+    .unwrap();
+    assert!(defs[0].word == 0xFC000000 && defs[0].operands.is_empty());
+    assert!(defs[1].word == 0x7000003F && defs[1].isa == IsaLevel::Mips32);
+
+    // a given word is used as is, without the opcode of its format
+    let defs = parse_extensions(
+        "core.toml",
+        "[[instruction]]\nname = \"brk1\"\nformat = \"cop1\"\nword = 0x0000000D",
+    )
+    .unwrap();
+    assert!(defs[0].word == 0x0000000D);
+
+    let defs = parse_extensions(
+        "core.json",
+        r#"{"instruction": [{"name": "ldi", "format": "i", "opcode": 59, "operands": "rt, imm", "imm": "unsigned"}]}"#,
+    )
+    .unwrap();
+    assert!(defs[0].word == 0xEC000000 && defs[0].imm == ImmKind::Unsigned);
+
+    for bad in [
+        "[[instruction]]\nname = \"x\"\nformat = \"r\"\nfunct = 64",
+        "[[instruction]]\nname = \"x\"\nformat = \"i\"",
+        "[[instruction]]\nname = \"x\"\nformat = \"q\"\nopcode = 1",
+        "[[instruction]]\nname = \"x\"\nformat = \"r\"\nrd = 3\noperands = \"rd, rs\"",
+        "[[instruction]]\nname = \"x\"\nformat = \"r\"\noperands = \"rz\"",
+        "[[instruction]]\nname = \"x\"\nformat = \"r\"\ncolor = 1",
+        "[[instruction]]\nname = \"x\"\nformat = \"r\"\nword = 0x0D\nfunct = 0x0D",
+    ] {
+        assert!(parse_extensions("bad.toml", bad).is_err(), "{bad}");
+    }
+}
@@ -0,0 +1,304 @@
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::SyntaxError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident(String),    // mnemonics, tags and directives (with their leading '.')
+    Register(String), // register name without the '$'
+    Integer(i64),     // numbers and char literals
+    Str(String),
+    Comma,
+    Colon,
+    LParen,
+    RParen,
+    Plus,
+    Minus,
+    Percent,
+    Star,
+    Slash,
+    Shl,
+    Shr,
+    Amp,
+    Pipe,
+    Caret,
+    Tilde,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/**
+ *  Splits a single source line into tokens. Comments run from '#' to the end of the line,
+ *  unless the '#' is inside a string or char literal
+ */
+pub fn tokenize(line: &Span) -> Result<Vec<Token>, Diagnostic> {
+    let text = line.line_text.as_str();
+    let bytes = text.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut idx = 0;
+
+    let at = |start: usize, end: usize| Span {
+        col_start: start + 1,
+        col_end: end + 1,
+        ..line.clone()
+    };
+
+    while idx < bytes.len() {
+        let c = bytes[idx] as char;
+        let start = idx;
+
+        let kind = match c {
+            '#' => break,
+            c if c.is_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            ',' => {
+                idx += 1;
+                TokenKind::Comma
+            }
+            ':' => {
+                idx += 1;
+                TokenKind::Colon
+            }
+            '(' => {
+                idx += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                idx += 1;
+                TokenKind::RParen
+            }
+            '+' => {
+                idx += 1;
+                TokenKind::Plus
+            }
+            '-' => {
+                idx += 1;
+                TokenKind::Minus
+            }
+            '%' => {
+                idx += 1;
+                TokenKind::Percent
+            }
+            '*' => {
+                idx += 1;
+                TokenKind::Star
+            }
+            '/' => {
+                idx += 1;
+                TokenKind::Slash
+            }
+            '&' => {
+                idx += 1;
+                TokenKind::Amp
+            }
+            '|' => {
+                idx += 1;
+                TokenKind::Pipe
+            }
+            '^' => {
+                idx += 1;
+                TokenKind::Caret
+            }
+            '~' => {
+                idx += 1;
+                TokenKind::Tilde
+            }
+            '<' | '>' | '=' | '!' => {
+                let next = bytes.get(idx + 1).map(|&b| b as char);
+                idx += 2;
+                match (c, next) {
+                    ('<', Some('<')) => TokenKind::Shl,
+                    ('>', Some('>')) => TokenKind::Shr,
+                    ('<', Some('=')) => TokenKind::Le,
+                    ('>', Some('=')) => TokenKind::Ge,
+                    ('=', Some('=')) => TokenKind::Eq,
+                    ('!', Some('=')) => TokenKind::Ne,
+                    ('<', _) => {
+                        idx -= 1;
+                        TokenKind::Lt
+                    }
+                    ('>', _) => {
+                        idx -= 1;
+                        TokenKind::Gt
+                    }
+                    _ => {
+                        return Err(Diagnostic::new(
+                            &at(start, start + 1),
+                            SyntaxError::UnexpectedCharacter(c),
+                        ))
+                    }
+                }
+            }
+            '$' => {
+                idx += 1;
+                while idx < bytes.len() && (bytes[idx] as char).is_ascii_alphanumeric() {
+                    idx += 1;
+                }
+                if idx == start + 1 {
+                    return Err(Diagnostic::new(
+                        &at(start, idx),
+                        SyntaxError::UnexpectedCharacter('$'),
+                    ));
+                }
+                TokenKind::Register(text[start + 1..idx].to_string())
+            }
+            '0'..='9' => {
+                while idx < bytes.len() && (bytes[idx] as char).is_ascii_alphanumeric() {
+                    idx += 1;
+                }
+                let literal = &text[start..idx];
+                TokenKind::Integer(
+                    parse_int_hex_or_dec(literal)
+                        .map_err(|e| Diagnostic::new(&at(start, idx), e))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                while idx < bytes.len() && is_ident_char(bytes[idx] as char) {
+                    idx += 1;
+                }
+                TokenKind::Ident(text[start..idx].to_string())
+            }
+            '"' => {
+                let (contents, end) = delimit(text, start, '"')
+                    .map_err(|e| Diagnostic::new(&at(start, text.len()), e))?;
+                idx = end;
+                TokenKind::Str(contents)
+            }
+            '\'' => {
+                let (contents, end) = delimit(text, start, '\'')
+                    .map_err(|e| Diagnostic::new(&at(start, text.len()), e))?;
+                idx = end;
+                let mut chars = contents.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => TokenKind::Integer(ch as i64),
+                    _ => {
+                        return Err(Diagnostic::new(
+                            &at(start, idx),
+                            SyntaxError::InvalidCharLiteral(contents),
+                        ))
+                    }
+                }
+            }
+            other => {
+                return Err(Diagnostic::new(
+                    &at(start, start + other.len_utf8()),
+                    SyntaxError::UnexpectedCharacter(other),
+                ))
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            span: at(start, idx),
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/**
+ *  Reads a quoted literal starting at `start`, resolving escape sequences.
+ *  Returns the contents and the index right after the closing quote
+ */
+fn delimit(text: &str, start: usize, quote: char) -> Result<(String, usize), SyntaxError> {
+    let mut contents = String::new();
+    let mut chars = text[start + 1..].char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((contents, start + 1 + offset + 1)),
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'r')) => '\r',
+                    Some((_, '0')) => '\0',
+                    Some((_, '\\')) => '\\',
+                    Some((_, '"')) => '"',
+                    Some((_, '\'')) => '\'',
+                    Some((_, other)) => return Err(SyntaxError::InvalidEscape(other)),
+                    None => break,
+                };
+                contents.push(escaped);
+            }
+            c => contents.push(c),
+        }
+    }
+
+    Err(SyntaxError::NoMatchingPair(quote))
+}
+
+/**
+ *  Converts a string representation of an integer into an actual integer
+ */
+pub fn parse_int_hex_or_dec(s: &str) -> Result<i64, SyntaxError> {
+    if s.starts_with("0X") || s.starts_with("0x") {
+        i64::from_str_radix(s.trim_start_matches("0x").trim_start_matches("0X"), 16)
+    } else if s.starts_with("0B") || s.starts_with("0b") {
+        i64::from_str_radix(s.trim_start_matches("0b").trim_start_matches("0B"), 2)
+    } else {
+        s.parse()
+    }
+    .map_err(|_| SyntaxError::InvalidInteger(s.into()))
+}
+
+#[test]
+fn strings_keep_their_contents() {
+    let line = Span::of_line(
+        "test.s",
+        1,
+        "msg: .asciiz \"cost: $a0, # not a comment\" # comment",
+    );
+    let tokens = tokenize(&line).unwrap();
+    let kinds: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+    assert!(
+        kinds
+            == [
+                TokenKind::Ident("msg".into()),
+                TokenKind::Colon,
+                TokenKind::Ident(".asciiz".into()),
+                TokenKind::Str("cost: $a0, # not a comment".into()),
+            ]
+    );
+}
+
+#[test]
+fn token_spans() {
+    let line = Span::of_line("test.s", 3, "\tlw $t0, -4($sp)");
+    let tokens = tokenize(&line).unwrap();
+    let cols: Vec<(usize, usize)> = tokens
+        .iter()
+        .map(|t| (t.span.col_start, t.span.col_end))
+        .collect();
+    assert!(
+        cols == [
+            (2, 4),
+            (5, 8),
+            (8, 9),
+            (10, 11),
+            (11, 12),
+            (12, 13),
+            (13, 16),
+            (16, 17)
+        ]
+    );
+    assert!(tokens[4].kind == TokenKind::Integer(4));
+
+    let bad = Span::of_line("test.s", 4, "addi $t0, $t0, 1 @");
+    assert!(tokenize(&bad).unwrap_err().span.unwrap().col_start == 18);
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::Structures::Diagnostic::{Diagnostic, MacroCall, Span};
+use crate::Structures::Errors::{MacroError, SyntaxError};
+
+/**
+ *  Invocations nested deeper than this are taken to be runaway recursion
+ */
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<(String, Option<String>)>, // name and default value
+    body: Vec<Span>,
+}
+
+/**
+ *  Expands `.macro`/`.endm` definitions. Works on whole lines, before they are parsed:
+ *  `\param` in the body is replaced by the text of its argument and `\@` by a number that
+ *  is unique to each expansion, so labels inside a macro can be told apart
+ */
+#[derive(Debug, Default)]
+pub struct MacroProcessor {
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+}
+
+impl MacroProcessor {
+    /**
+     *  Whether `name`, in lower case, has been defined as a macro
+     */
+    pub fn is_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    /**
+     *  Reads a definition: the `.macro name p1, p2=default` header on `line` and every
+     *  following line up to the matching `.endm`
+     */
+    pub fn define(
+        &mut self,
+        line: &Span,
+        col: usize,
+        lines: &mut impl Iterator<Item = Span>,
+    ) -> Result<(), Diagnostic> {
+        let header = strip_comment(&line.line_text[col + ".macro".len()..]).trim();
+        let name_len = header
+            .find(|c: char| c.is_whitespace() || c == ',')
+            .unwrap_or(header.len());
+        let name = header[..name_len].to_ascii_lowercase();
+
+        // the body is collected even if the header is broken, so it is not assembled
+        let mut body = Vec::new();
+        let mut nesting = 0;
+        let mut terminated = false;
+        for l in lines.by_ref() {
+            match head(&l.line_text).map(|(_, w)| w.to_ascii_lowercase()) {
+                Some(w) if w == ".macro" => nesting += 1,
+                Some(w) if w == ".endm" && nesting == 0 => {
+                    terminated = true;
+                    break;
+                }
+                Some(w) if w == ".endm" => nesting -= 1,
+                _ => {}
+            }
+            body.push(l);
+        }
+
+        if name.is_empty() || !is_identifier(&name) {
+            return Err(Diagnostic::new(
+                line,
+                SyntaxError::Expected("macro name".into()),
+            ));
+        }
+        if !terminated {
+            return Err(Diagnostic::new(line, MacroError::Unterminated(name)));
+        }
+        if self.macros.contains_key(&name) {
+            // looked for after the directive, the name may be part of ".macro" itself
+            let after = Span {
+                col_start: col + ".macro".len() + 1,
+                ..line.clone()
+            };
+            return Err(Diagnostic::new(
+                &after.narrow(&name),
+                MacroError::Redefinition(name),
+            ));
+        }
+
+        let mut params = Vec::new();
+        for param in header[name_len..]
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let (p, default) = match param.split_once('=') {
+                Some((p, default)) => (p.trim(), Some(default.trim().to_string())),
+                None => (param, None),
+            };
+            if !is_identifier(p) {
+                return Err(Diagnostic::new(
+                    &line.narrow(param),
+                    MacroError::InvalidParameter(p.into()),
+                ));
+            }
+            params.push((p.to_string(), default));
+        }
+
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    /**
+     *  Binds the arguments of the invocation on `line` and returns the body with them
+     *  substituted. Any tags before the invocation are kept on a line of their own
+     */
+    pub fn invoke(&mut self, line: &Span, col: usize, name: &str) -> Result<Vec<Span>, Diagnostic> {
+        let def = self.macros[name].clone();
+        let text = &line.line_text;
+        let args_text = strip_comment(&text[col + name.len()..]);
+
+        let mut values: Vec<Option<String>> = def.params.iter().map(|(_, d)| d.clone()).collect();
+        let mut position = 0;
+        for arg in split_args(args_text) {
+            // `param=value` binds by name, anything else takes the next position
+            let keyword = arg
+                .split_once('=')
+                .map(|(p, v)| (p.trim(), v.trim()))
+                .filter(|(p, _)| is_identifier(p));
+            match keyword {
+                Some((p, v)) => match def.params.iter().position(|(param, _)| param == p) {
+                    Some(i) => values[i] = Some(v.to_string()),
+                    None => {
+                        return Err(Diagnostic::new(
+                            &line.narrow(arg),
+                            MacroError::UnknownParameter(p.into(), name.into()),
+                        ))
+                    }
+                },
+                None if position >= def.params.len() => {
+                    return Err(Diagnostic::new(
+                        &line.narrow(arg),
+                        MacroError::TooManyArguments(name.into()),
+                    ))
+                }
+                None => {
+                    // an empty argument keeps the default
+                    if !arg.is_empty() {
+                        values[position] = Some(arg.to_string());
+                    }
+                    position += 1;
+                }
+            }
+        }
+        let values: Vec<String> = values
+            .into_iter()
+            .zip(&def.params)
+            .map(|(v, (param, _))| {
+                v.ok_or_else(|| {
+                    Diagnostic::new(
+                        line,
+                        MacroError::MissingArgument(param.clone(), name.into()),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        self.expansions += 1;
+        let call = Arc::new(MacroCall {
+            name: name.into(),
+            span: Span {
+                col_start: col + 1,
+                ..line.clone()
+            },
+        });
+
+        let mut expanded: Vec<Span> = tags_before(line, col).into_iter().collect();
+        for body_line in &def.body {
+            let substituted =
+                substitute(&body_line.line_text, &def.params, &values, self.expansions);
+            expanded.push(Span {
+                expansion: Some(call.clone()),
+                ..Span::of_line(&body_line.file, body_line.line, &substituted)
+            });
+        }
+        Ok(expanded)
+    }
+}
+
+/**
+ *  Byte offset and text of the first word of a line, after any `tag:` definitions
+ */
+pub fn head(text: &str) -> Option<(usize, &str)> {
+    let mut idx = 0;
+    loop {
+        let start = idx + (text[idx..].len() - text[idx..].trim_start().len());
+        let len = text[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(text.len() - start);
+        if len == 0 {
+            return None;
+        }
+        let after = &text[start + len..];
+        match after.trim_start().strip_prefix(':') {
+            Some(rest) => idx = text.len() - rest.len(),
+            None => return Some((start, &text[start..start + len])),
+        }
+    }
+}
+
+/**
+ *  The `tag:` definitions in front of the word at `col`, as a line of their own
+ */
+pub fn tags_before(line: &Span, col: usize) -> Option<Span> {
+    let text = &line.line_text;
+    if text[..col].trim().is_empty() {
+        return None;
+    }
+    let tags = format!("{}{}", &text[..col], " ".repeat(text.len() - col));
+    Some(Span {
+        expansion: line.expansion.clone(),
+        ..Span::of_line(&line.file, line.line, &tags)
+    })
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/**
+ *  Drops a trailing `#` comment, leaving any '#' inside string or char literals alone
+ */
+pub fn strip_comment(text: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &text[..idx],
+            _ => {}
+        }
+    }
+    text
+}
+
+/**
+ *  Splits invocation arguments on the commas that are not inside parentheses or literals
+ */
+fn split_args(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                args.push(text[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(text[start..].trim());
+    args
+}
+
+/**
+ *  Replaces `\param` with its argument and `\@` with the expansion number. A backslash
+ *  that is not followed by a parameter name is left alone, so escapes in strings survive
+ */
+fn substitute(
+    text: &str,
+    params: &[(String, Option<String>)],
+    values: &[String],
+    expansion: usize,
+) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(tail) = after.strip_prefix('@') {
+            out.push_str(&expansion.to_string());
+            rest = tail;
+            continue;
+        }
+        let len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        match params.iter().position(|(p, _)| *p == after[..len]) {
+            Some(i) if len > 0 => {
+                out.push_str(&values[i]);
+                rest = &after[len..];
+            }
+            _ => {
+                out.push('\\');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn macro_expansion() {
+    use crate::Functionality::TagResolution::SymbolTable;
+
+    let src = ".macro push reg\naddi $sp, $sp, -4\nsw \\reg, 0($sp)\n.endm\n.macro spin n=1, reg=$t0\nwait\\@: addi \\reg, \\reg, -\\n # \\n\nbne \\reg, $zero, wait\\@\n.endm\nstart: push $ra\nspin\nspin reg=$t1, n=2\n";
+    let mut errors = Vec::new();
+    let expanded = crate::Functionality::Preprocessor::Preprocessor::new(&[], SymbolTable::new())
+        .source(
+            crate::Functionality::Parser::lines(src, "test.s"),
+            &mut errors,
+        );
+    let texts: Vec<&str> = expanded
+        .iter()
+        .map(|l| l.line_text.trim_end())
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    assert!(errors.is_empty());
+    assert!(
+        texts
+            == [
+                "start:",
+                "addi $sp, $sp, -4",
+                "sw $ra, 0($sp)",
+                "wait2: addi $t0, $t0, -1 # 1",
+                "bne $t0, $zero, wait2",
+                "wait3: addi $t1, $t1, -2 # 2",
+                "bne $t1, $zero, wait3",
+            ]
+    );
+    let call = expanded[2].expansion.as_ref().unwrap();
+    assert!(expanded[2].line == 3 && call.name == "push" && call.span.line == 9);
+}
+
+#[test]
+fn macros() {
+    use crate::Functionality::Assembler::Assembler;
+
+    let src = ".macro inc reg, n=1\naddi \\reg, \\reg, \\n\n.endm\n.macro twice reg\ninc \\reg\ninc \\reg, n=2\n.endm\n.text 0x0\ntwice $t0\nloop: inc $t1, -1\nbne $t1, $zero, loop";
+    let program = Assembler::new()
+        .add_source("test.s", src)
+        .assemble()
+        .unwrap();
+    assert!(program.code == [0x21080001, 0x21080002, 0x2129FFFF, 0x1520FFFE]);
+
+    let errors = Assembler::new()
+        .add_source("test.s", &format!("{src}\ninc $t2, 0x10000\n.endm"))
+        .assemble()
+        .unwrap_err();
+    let span = errors[1].span.as_ref().unwrap();
+    let call = span.expansion.as_ref().unwrap();
+    assert!(span.line == 2 && call.name == "inc" && call.span.line == 12);
+    assert!(errors[1]
+        .to_string()
+        .contains("in expansion of macro \"inc\""));
+    assert!(errors[0].span.as_ref().unwrap().line == 13);
+
+    // the name of a redefined macro is pointed at, even when it also appears in ".macro"
+    let errors = Assembler::new()
+        .add_source("test.s", ".macro mac\n.endm\n.macro mac\n.endm")
+        .assemble()
+        .unwrap_err();
+    let span = errors[0].span.as_ref().unwrap();
+    assert!(span.line == 3 && span.col_start == 8 && span.text() == "mac");
+}
@@ -0,0 +1,498 @@
+use super::Lexer::{tokenize, Token, TokenKind};
+
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::{CompileError, SyntaxError};
+use crate::Structures::Expression::{BinaryOp, Expr, UnaryOp};
+use crate::Structures::Register::{CopRegister, Register};
+use crate::Structures::Statement::{Operand, OperandKind, Statement, StatementKind};
+
+/**
+ *  Parses a whole source into statements. A line that fails to parse does not stop the
+ *  parser: its error is collected and parsing continues with the next line
+ */
+pub fn parse(text: &str, file: &str) -> (Vec<Statement>, Vec<Diagnostic>) {
+    parse_lines(&lines(text, file))
+}
+
+/**
+ *  Splits a source into one span per line
+ */
+pub fn lines(text: &str, file: &str) -> Vec<Span> {
+    text.split('\n')
+        .enumerate()
+        .map(|(idx, line)| Span::of_line(file, idx + 1, line.trim_end_matches('\r')))
+        .collect()
+}
+
+pub fn parse_lines(lines: &[Span]) -> (Vec<Statement>, Vec<Diagnostic>) {
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut errors: Vec<Diagnostic> = Vec::new();
+
+    for span in lines {
+        if let Err(e) = parse_line(span, &mut statements) {
+            errors.push(e);
+        }
+    }
+
+    (statements, errors)
+}
+
+/**
+ *  Parses a single line: any number of `tag:` definitions followed by at most one
+ *  directive or instruction
+ */
+pub fn parse_line(line: &Span, statements: &mut Vec<Statement>) -> Result<(), Diagnostic> {
+    let tokens = match tokenize(line) {
+        Ok(t) => t,
+        Err(e) => {
+            if looks_like_instruction(&line.line_text) {
+                statements.push(Statement {
+                    kind: StatementKind::Invalid,
+                    span: line.clone(),
+                });
+            }
+            return Err(e);
+        }
+    };
+    let mut cursor = Cursor {
+        tokens,
+        pos: 0,
+        line,
+    };
+
+    while let (Some(TokenKind::Ident(name)), Some(TokenKind::Colon)) =
+        (cursor.peek_kind(0), cursor.peek_kind(1))
+    {
+        let name = name.clone();
+        let span = cursor.tokens[cursor.pos]
+            .span
+            .to(&cursor.tokens[cursor.pos + 1].span);
+        cursor.pos += 2;
+        statements.push(Statement {
+            kind: StatementKind::Label(name),
+            span,
+        });
+    }
+
+    let head = match cursor.next() {
+        None => return Ok(()),
+        Some(t) => t,
+    };
+
+    match head.kind {
+        TokenKind::Ident(name) if name.starts_with('.') => {
+            let operands = cursor.operands()?;
+            statements.push(Statement {
+                span: cursor.span_from(&head.span),
+                kind: StatementKind::Directive(name.to_ascii_lowercase(), operands),
+            });
+            Ok(())
+        }
+        TokenKind::Ident(name) => match cursor.operands() {
+            Ok(operands) => {
+                statements.push(Statement {
+                    span: cursor.span_from(&head.span),
+                    kind: StatementKind::Instruction(name.to_ascii_lowercase(), operands),
+                });
+                Ok(())
+            }
+            Err(e) => {
+                statements.push(Statement {
+                    span: cursor.span_from(&head.span),
+                    kind: StatementKind::Invalid,
+                });
+                Err(e)
+            }
+        },
+        other => Err(Diagnostic::new(
+            &head.span,
+            SyntaxError::UnexpectedToken(other.to_string()),
+        )),
+    }
+}
+
+/**
+ *  Best effort guess used when a line cannot even be tokenized, so that a broken
+ *  instruction still reserves its slot
+ */
+fn looks_like_instruction(text: &str) -> bool {
+    let body = text.rsplit(':').next().unwrap_or("").trim_start();
+    body.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+struct Cursor<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: &'a Span,
+}
+
+impl Cursor<'_> {
+    fn peek_kind(&self, ahead: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + ahead).map(|t| &t.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    /**
+     *  Span from `start` up to the last consumed token
+     */
+    fn span_from(&self, start: &Span) -> Span {
+        match self.tokens.get(self.pos.min(self.tokens.len()) - 1) {
+            Some(last) => start.to(&last.span),
+            None => start.clone(),
+        }
+    }
+
+    /**
+     *  Span pointing right after the last token, for things that are missing
+     */
+    fn end_span(&self) -> Span {
+        let col = self.tokens.last().map(|t| t.span.col_end).unwrap_or(1);
+        Span {
+            col_start: col,
+            col_end: col + 1,
+            ..self.line.clone()
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, Diagnostic> {
+        match self.next() {
+            Some(t) if t.kind == kind => Ok(t),
+            Some(t) => Err(Diagnostic::new(&t.span, SyntaxError::Expected(what.into()))),
+            None => Err(Diagnostic::new(
+                &self.end_span(),
+                SyntaxError::Expected(what.into()),
+            )),
+        }
+    }
+
+    /**
+     *  Comma separated operands up to the end of the line
+     */
+    fn operands(&mut self) -> Result<Vec<Operand>, Diagnostic> {
+        let mut operands: Vec<Operand> = Vec::new();
+        if self.peek_kind(0).is_none() {
+            return Ok(operands);
+        }
+
+        operands.push(self.operand()?);
+        while let Some(t) = self.next() {
+            if t.kind != TokenKind::Comma {
+                return Err(Diagnostic::new(
+                    &t.span,
+                    SyntaxError::UnexpectedToken(t.kind.to_string()),
+                ));
+            }
+            operands.push(self.operand()?);
+        }
+
+        Ok(operands)
+    }
+
+    fn operand(&mut self) -> Result<Operand, Diagnostic> {
+        let head = match self.tokens.get(self.pos) {
+            Some(t) => t.clone(),
+            None => {
+                return Err(Diagnostic::new(
+                    &self.end_span(),
+                    SyntaxError::MissingOperand,
+                ))
+            }
+        };
+
+        let kind = match head.kind {
+            TokenKind::Register(ref name) => {
+                self.pos += 1;
+                match (CopRegister::parse_cp0(name), CopRegister::parse_fpr(name)) {
+                    (Some(r), _) => OperandKind::CopRegister(r),
+                    (_, Some(r)) => OperandKind::FpRegister(r),
+                    _ => OperandKind::Register(self.register(name, &head.span)?),
+                }
+            }
+            TokenKind::Str(s) => {
+                self.pos += 1;
+                OperandKind::Str(s)
+            }
+            // `($base)` has an implicit offset of 0, any other '(' opens an expression
+            TokenKind::LParen if matches!(self.peek_kind(1), Some(TokenKind::Register(_))) => {
+                self.maybe_memory(OperandKind::Imm(0))?
+            }
+            TokenKind::Percent => {
+                self.pos += 1;
+                let half = self.relocation()?;
+                self.maybe_memory(half)?
+            }
+            _ => {
+                let expr = self.expr()?;
+                let value = value_of(expr, &self.span_from(&head.span))?;
+                self.maybe_memory(value)?
+            }
+        };
+
+        Ok(Operand {
+            kind,
+            span: self.span_from(&head.span),
+        })
+    }
+
+    /**
+     *  `%hi(x)` or `%lo(x)`, with the leading '%' already consumed. The half is taken at
+     *  code generation, also for a constant `x`
+     */
+    fn relocation(&mut self) -> Result<OperandKind, Diagnostic> {
+        let hi = match self.next() {
+            Some(Token {
+                kind: TokenKind::Ident(op),
+                ..
+            }) if op.eq_ignore_ascii_case("hi") || op.eq_ignore_ascii_case("lo") => {
+                op.eq_ignore_ascii_case("hi")
+            }
+            Some(t) => {
+                return Err(Diagnostic::new(
+                    &t.span,
+                    SyntaxError::Expected("%hi or %lo".into()),
+                ))
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    &self.end_span(),
+                    SyntaxError::Expected("%hi or %lo".into()),
+                ))
+            }
+        };
+        let open = self.expect(TokenKind::LParen, "'('")?;
+        let expr = self.expr()?;
+        self.expect(TokenKind::RParen, "')'")?;
+
+        Ok(match value_of(expr, &self.span_from(&open.span))? {
+            // a half is a raw bit pattern, which fits signed and unsigned fields alike
+            OperandKind::Imm(v) => half(hi, Expr::Num(v)),
+            OperandKind::Label(tag) => half(hi, Expr::Symbol(tag)),
+            OperandKind::Expr(e) => half(hi, e),
+            _ => unreachable!(),
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, Diagnostic> {
+        self.binary(0)
+    }
+
+    /**
+     *  Binary operators binding at least as tight as `min_precedence`, left to right
+     */
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, Diagnostic> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.peek_kind(0).and_then(binary_op) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Diagnostic> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Minus,
+                ..
+            }) => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?))),
+            Some(Token {
+                kind: TokenKind::Tilde,
+                ..
+            }) => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Some(Token {
+                kind: TokenKind::Plus,
+                ..
+            }) => self.unary(),
+            Some(Token {
+                kind: TokenKind::Integer(v),
+                ..
+            }) => Ok(Expr::Num(v)),
+            Some(Token {
+                kind: TokenKind::Ident(name),
+                ..
+            }) => Ok(Expr::Symbol(name)),
+            Some(Token {
+                kind: TokenKind::LParen,
+                ..
+            }) => {
+                let inner = self.expr()?;
+                self.expect(TokenKind::RParen, "')'")?;
+                Ok(inner)
+            }
+            Some(t) => Err(Diagnostic::new(
+                &t.span,
+                SyntaxError::UnexpectedToken(t.kind.to_string()),
+            )),
+            None => Err(Diagnostic::new(
+                &self.end_span(),
+                SyntaxError::MissingOperand,
+            )),
+        }
+    }
+
+    /**
+     *  A value is either an operand on its own or the offset of an `offset(base)` operand
+     */
+    fn maybe_memory(&mut self, offset: OperandKind) -> Result<OperandKind, Diagnostic> {
+        if self.peek_kind(0) != Some(&TokenKind::LParen) {
+            return Ok(offset);
+        }
+        self.expect(TokenKind::LParen, "'('")?;
+        let base = match self.next() {
+            Some(Token {
+                kind: TokenKind::Register(name),
+                span,
+            }) => self.register(&name, &span)?,
+            Some(t) => {
+                return Err(Diagnostic::new(
+                    &t.span,
+                    SyntaxError::Expected("register".into()),
+                ))
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    &self.end_span(),
+                    SyntaxError::Expected("register".into()),
+                ))
+            }
+        };
+        self.expect(TokenKind::RParen, "')'")?;
+        Ok(OperandKind::Memory(Box::new(offset), base))
+    }
+
+    fn register(&self, name: &str, span: &Span) -> Result<Register, Diagnostic> {
+        Register::parse(name).map_err(|e| Diagnostic::new(span, e))
+    }
+}
+
+fn binary_op(kind: &TokenKind) -> Option<BinaryOp> {
+    Some(match kind {
+        TokenKind::Star => BinaryOp::Mul,
+        TokenKind::Slash => BinaryOp::Div,
+        TokenKind::Percent => BinaryOp::Rem,
+        TokenKind::Plus => BinaryOp::Add,
+        TokenKind::Minus => BinaryOp::Sub,
+        TokenKind::Shl => BinaryOp::Shl,
+        TokenKind::Shr => BinaryOp::Shr,
+        TokenKind::Lt => BinaryOp::Lt,
+        TokenKind::Gt => BinaryOp::Gt,
+        TokenKind::Le => BinaryOp::Le,
+        TokenKind::Ge => BinaryOp::Ge,
+        TokenKind::Eq => BinaryOp::Eq,
+        TokenKind::Ne => BinaryOp::Ne,
+        TokenKind::Amp => BinaryOp::And,
+        TokenKind::Caret => BinaryOp::Xor,
+        TokenKind::Pipe => BinaryOp::Or,
+        _ => return None,
+    })
+}
+
+/**
+ *  Folds an expression without symbols into an immediate. A lone symbol stays a tag,
+ *  anything else is evaluated once every tag has an address. Folding does not change what
+ *  a branch or jump target means, numbers are addresses there just like tags
+ */
+fn value_of(expr: Expr, span: &Span) -> Result<OperandKind, Diagnostic> {
+    match expr {
+        Expr::Symbol(tag) => Ok(OperandKind::Label(tag)),
+        e if e.has_symbols() => Ok(OperandKind::Expr(e)),
+        e => e
+            .eval(&|tag| Err(CompileError::TagResolution(tag.into())))
+            .map(OperandKind::Imm)
+            .map_err(|err| Diagnostic::new(span, err)),
+    }
+}
+
+fn half(hi: bool, expr: Expr) -> OperandKind {
+    if hi {
+        OperandKind::Hi(expr)
+    } else {
+        OperandKind::Lo(expr)
+    }
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Ident(s) => write!(f, "\"{s}\""),
+            TokenKind::Register(s) => write!(f, "\"${s}\""),
+            TokenKind::Integer(v) => write!(f, "\"{v}\""),
+            TokenKind::Str(s) => write!(f, "string \"{s}\""),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Percent => write!(f, "'%'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Shl => write!(f, "'<<'"),
+            TokenKind::Shr => write!(f, "'>>'"),
+            TokenKind::Amp => write!(f, "'&'"),
+            TokenKind::Pipe => write!(f, "'|'"),
+            TokenKind::Caret => write!(f, "'^'"),
+            TokenKind::Tilde => write!(f, "'~'"),
+            TokenKind::Lt => write!(f, "'<'"),
+            TokenKind::Gt => write!(f, "'>'"),
+            TokenKind::Le => write!(f, "'<='"),
+            TokenKind::Ge => write!(f, "'>='"),
+            TokenKind::Eq => write!(f, "'=='"),
+            TokenKind::Ne => write!(f, "'!='"),
+        }
+    }
+}
+
+#[test]
+fn statements() {
+    let (statements, errors) = parse(
+        "loop: lw $t0, -4($sp) # load\n.data 0x100\nmsg: .asciiz \"a, b\"\n\nj loop",
+        "test.s",
+    );
+    assert!(errors.is_empty());
+
+    let kinds: Vec<StatementKind> = statements.into_iter().map(|s| s.kind).collect();
+    assert!(matches!(&kinds[0], StatementKind::Label(l) if l == "loop"));
+    match &kinds[1] {
+        StatementKind::Instruction(m, ops) => {
+            assert!(m == "lw");
+            assert!(ops[0].kind == OperandKind::Register(Register::parse("t0").unwrap()));
+            assert!(
+                ops[1].kind
+                    == OperandKind::Memory(
+                        Box::new(OperandKind::Imm(-4)),
+                        Register::parse("sp").unwrap()
+                    )
+            );
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(
+        matches!(&kinds[2], StatementKind::Directive(d, ops) if d == ".data" && ops[0].kind == OperandKind::Imm(0x100))
+    );
+    assert!(matches!(&kinds[3], StatementKind::Label(l) if l == "msg"));
+    assert!(
+        matches!(&kinds[4], StatementKind::Directive(d, ops) if d == ".asciiz" && ops[0].kind == OperandKind::Str("a, b".into()))
+    );
+    assert!(
+        matches!(&kinds[5], StatementKind::Instruction(m, ops) if m == "j" && ops[0].kind == OperandKind::Label("loop".into()))
+    );
+}
+
+#[test]
+fn bad_lines_keep_their_slot() {
+    let (statements, errors) = parse("addi $t0, $t0 1\nadd $t0, $q1, $t2\nnop", "test.s");
+    assert!(errors.len() == 2);
+    assert!(errors[1].message.contains("$q1") && errors[1].span.as_ref().unwrap().col_start == 10);
+    assert!(matches!(statements[0].kind, StatementKind::Invalid));
+    assert!(matches!(statements[1].kind, StatementKind::Invalid));
+    assert!(matches!(statements[2].kind, StatementKind::Instruction(..)));
+}
@@ -0,0 +1,357 @@
+use crate::Structures::Errors::AssemblyWarning;
+use crate::Structures::Instruction::Instruction;
+use crate::Structures::InstructionTable::{Field, ImmKind};
+use crate::Structures::Register::Register;
+
+use super::CodeGen::SourceLine;
+
+/**
+ *  Branches and jumps execute the instruction that follows them (the delay slot)
+ *  before control is transferred
+ */
+pub fn has_delay_slot(instr: &Instruction) -> bool {
+    matches!(instr.def.imm, ImmKind::Branch | ImmKind::Jump)
+        || matches!(instr.def.mnemonic.as_str(), "jr" | "jalr")
+}
+
+/**
+ *  General purpose register written by `instr`, if any. Linking branches write $ra,
+ *  or rd for jalr
+ */
+pub fn writes(instr: &Instruction) -> Option<Register> {
+    let (def, args) = (&instr.def, &instr.args);
+    match def.mnemonic.as_str() {
+        "jal" | "bltzal" | "bgezal" => return Some(Register::RA),
+        "jalr" => return Some(args.rd),
+        _ => {}
+    }
+    if def
+        .operands
+        .iter()
+        .any(|f| matches!(f, Field::Rd | Field::RdRt))
+    {
+        return Some(args.rd);
+    }
+    writes_rt(instr).then_some(args.rt)
+}
+
+/**
+ *  Whether the rt field of `instr` is its destination: it leads the operands of loads,
+ *  lui, mfc* and the I-type ALU operations. Stores and mtc* name it first too, but only
+ *  read it. sc stores rt, then overwrites it with whether the store succeeded
+ */
+fn writes_rt(instr: &Instruction) -> bool {
+    let def = &instr.def;
+    let stores = matches!(
+        def.mnemonic.as_str(),
+        "sb" | "sh" | "sw" | "swl" | "swr" | "swc1"
+    );
+    def.operands.first() == Some(&Field::Rt) && !stores && !def.mnemonic.starts_with("mt")
+}
+
+/**
+ *  General purpose registers read by `instr`
+ */
+pub fn reads(instr: &Instruction) -> Vec<Register> {
+    let (def, args) = (&instr.def, &instr.args);
+    let mut regs = Vec::new();
+    for field in &def.operands {
+        match field {
+            Field::Rs | Field::Mem => regs.push(args.rs),
+            // partial loads merge into the old value of rt
+            Field::Rt if !writes_rt(instr) || matches!(def.mnemonic.as_str(), "lwl" | "lwr") => {
+                regs.push(args.rt)
+            }
+            _ => {}
+        }
+    }
+    regs
+}
+
+/**
+ *  Whether `instr` can be moved into a delay slot without changing what it does. Only
+ *  plain ALU operations and stores qualify; anything that traps, touches a coprocessor or
+ *  transfers control stays where it was written. Loads and mfhi/mflo stay too: the
+ *  instruction at the branch target would follow them, and it cannot be checked for hazards
+ */
+fn movable(instr: &Instruction) -> bool {
+    use Field::*;
+
+    let def = &instr.def;
+    let m = def.mnemonic.as_str();
+    match def.operands.as_slice() {
+        [Rd, Rs, Rt] | [Rd, Rt, Rs] | [Rd, Rt, Sa] => !matches!(m, "movz" | "movn"),
+        [Rt, Rs, Imm] | [Rt, Imm] => true,
+        [Rt, Mem] => delayed_load(instr).is_none() && m != "sc",
+        [Rs] => matches!(m, "mthi" | "mtlo"),
+        [Rs, Rt] => ["mult", "div", "madd", "msub"]
+            .iter()
+            .any(|p| m.starts_with(p)),
+        _ => false,
+    }
+}
+
+/**
+ *  Looks for an instruction to fill the delay slot of `branch`, which is about to be
+ *  appended to `instr_v`. The last instruction is taken out of `instr_v` if no tag sits
+ *  between it and `branch`, it is not itself in a delay slot, `branch` does not depend on
+ *  it and moving it does not leave `branch` right after a load whose result it reads
+ */
+pub fn take_slot_filler(
+    instr_v: &mut Vec<SourceLine<Instruction>>,
+    branch: &Instruction,
+) -> Option<SourceLine<Instruction>> {
+    let n = instr_v.len();
+    let prev = match instr_v.last() {
+        Some((_, Some(prev))) => prev,
+        _ => return None,
+    };
+    if !movable(prev) {
+        return None;
+    }
+
+    let branch_reads = reads(branch);
+    // tags in between do not separate instructions in the pipeline
+    let before = instr_v[..n - 1].iter().rev().find_map(|(_, i)| i.as_ref());
+    if let Some(before) = before {
+        let load_use =
+            delayed_load(before).is_some_and(|r| r != Register::ZERO && branch_reads.contains(&r));
+        if has_delay_slot(before) || load_use {
+            return None;
+        }
+    }
+
+    let conflict = match (writes(prev), writes(branch)) {
+        (Some(w), _) if w != Register::ZERO && branch_reads.contains(&w) => true,
+        // the link register is written before the delay slot runs
+        (w, Some(link)) => w == Some(link) || reads(prev).contains(&link),
+        _ => false,
+    };
+    if conflict {
+        return None;
+    }
+    instr_v.pop()
+}
+
+/**
+ *  Instructions whose result only becomes available one instruction later: loads and
+ *  moves from a coprocessor
+ */
+fn delayed_load(instr: &Instruction) -> Option<Register> {
+    let def = &instr.def;
+    match def.operands.as_slice() {
+        [Field::Rt, Field::Mem] if def.mnemonic.starts_with('l') => Some(instr.args.rt),
+        [Field::Rt, _] if matches!(def.mnemonic.as_str(), "mfc0" | "mfc1") => Some(instr.args.rt),
+        _ => None,
+    }
+}
+
+fn writes_hilo(instr: &Instruction) -> bool {
+    let m = instr.def.mnemonic.as_str();
+    matches!(m, "mthi" | "mtlo")
+        || (["mult", "div", "madd", "msub"]
+            .iter()
+            .any(|p| m.starts_with(p))
+            && instr.def.operands.as_slice() == [Field::Rs, Field::Rt])
+}
+
+/**
+ *  Checks `instr` against the instructions just before it in `instr_v` for R3000 pipeline
+ *  hazards: using a register in the instruction right after its load, or writing HI/LO
+ *  within two instructions of an mfhi/mflo. Returns the hazard with the number of nops
+ *  that would avoid it
+ */
+pub fn hazard(
+    instr_v: &[SourceLine<Instruction>],
+    instr: &Instruction,
+) -> Option<(usize, AssemblyWarning)> {
+    let mut previous = instr_v
+        .iter()
+        .rev()
+        .filter_map(|(span, i)| i.as_ref().map(|i| (span, i)));
+
+    let (prev_span, prev) = previous.next()?;
+    // the unaligned load pair lwl/lwr is interlocked on the register they both fill
+    let unaligned_pair = matches!(
+        (prev.def.mnemonic.as_str(), instr.def.mnemonic.as_str()),
+        ("lwl", "lwr") | ("lwr", "lwl")
+    ) && prev.args.rt == instr.args.rt;
+    if let Some(reg) = delayed_load(prev) {
+        if reg != Register::ZERO && !unaligned_pair && reads(instr).contains(&reg) {
+            return Some((
+                1,
+                AssemblyWarning::LoadDelay(reg.to_string(), prev_span.line),
+            ));
+        }
+    }
+
+    if writes_hilo(instr) {
+        let before = previous.next();
+        for (distance, (span, i)) in std::iter::once((prev_span, prev)).chain(before).enumerate() {
+            if matches!(i.def.mnemonic.as_str(), "mfhi" | "mflo") {
+                return Some((
+                    2 - distance,
+                    AssemblyWarning::HiLoHazard(
+                        instr.def.mnemonic.clone(),
+                        i.def.mnemonic.clone(),
+                        span.line,
+                    ),
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn delay_slot_candidates() {
+    use crate::Structures::Diagnostic::Span;
+    use crate::Structures::Instruction::parse_instruction;
+
+    // previous lines are separated by ';', a line ending in ':' stands for a tag
+    let slot = |prev: &str, branch: &str| {
+        let mut instr_v: Vec<_> = prev
+            .split(';')
+            .map(|l| {
+                let l = l.trim();
+                let instr = (!l.ends_with(':')).then(|| parse_instruction(l).unwrap());
+                (Span::of_line("test.s", 1, l), instr)
+            })
+            .collect();
+        take_slot_filler(&mut instr_v, &parse_instruction(branch).unwrap()).is_some()
+    };
+
+    assert!(slot("addi $t0, $t0, 1", "beq $t1, $zero, 0"));
+    assert!(!slot("lw $t2, 0($sp)", "j 0"));
+    assert!(!slot("mflo $t2", "jr $ra"));
+    assert!(slot("sw $t2, 0($sp)", "j 0"));
+    assert!(!slot("addi $t0, $t0, 1", "beq $t0, $zero, 0"));
+    assert!(!slot("slt $at, $t0, $t1", "bne $at, $zero, 0"));
+    assert!(!slot("addu $a0, $ra, $zero", "jal 0"));
+    assert!(!slot("syscall", "j 0"));
+    assert!(!slot("teq $t0, $t1", "j 0"));
+    assert!(!slot("slti $t0, $t1, 5", "beq $t0, $zero, 0"));
+    assert!(slot("sw $t0, 0($sp)", "beq $t0, $zero, 0"));
+    assert!(!slot(
+        "lw $t0, 0($a0); addu $t1, $t2, $t3",
+        "beq $t0, $zero, 0"
+    ));
+    assert!(!slot(
+        "lw $t0, 0($a0); top:; addu $t1, $t2, $t3",
+        "beq $t0, $zero, 0"
+    ));
+    assert!(slot(
+        "lw $t0, 0($a0); addu $t1, $t2, $t3",
+        "beq $t4, $zero, 0"
+    ));
+    assert!(!slot("j 0; top:; addu $t1, $t2, $t3", "j 0"));
+    assert!(!slot("addu $t1, $t2, $t3; top:", "j 0"));
+}
+
+#[test]
+fn pipeline_hazards() {
+    use crate::Structures::Diagnostic::Span;
+    use crate::Structures::Instruction::parse_instruction;
+
+    let nops = |before: &[&str], instr: &str| {
+        let instr_v: Vec<_> = before
+            .iter()
+            .map(|l| (Span::of_line("test.s", 1, l), parse_instruction(l).ok()))
+            .collect();
+        hazard(&instr_v, &parse_instruction(instr).unwrap()).map(|(n, _)| n)
+    };
+
+    assert!(nops(&["lw $t0, 0($sp)"], "addi $t1, $t0, 1") == Some(1));
+    assert!(nops(&["lw $t0, 0($sp)"], "sw $t0, 4($sp)") == Some(1));
+    assert!(nops(&["lw $t0, 0($sp)"], "addi $t1, $t2, 1").is_none());
+    assert!(nops(&["lw $t0, 0($sp)"], "addu $t0, $t1, $t0") == Some(1));
+    assert!(nops(&["lw $t0, 0($sp)"], "sllv $t0, $t0, $t0") == Some(1));
+    assert!(nops(&["lw $t0, 0($sp)", "nop"], "addi $t1, $t0, 1").is_none());
+    assert!(nops(&["mflo $t0"], "mult $t1, $t2") == Some(2));
+    assert!(nops(&["mfhi $t0", "nop"], "div $t1, $t2") == Some(1));
+    assert!(nops(&["mfhi $t0", "nop", "nop"], "div $t1, $t2").is_none());
+    assert!(nops(&["lw $t0, 0($sp)"], "slti $t1, $t2, 5").is_none());
+    assert!(nops(&["lw $t0, 0($sp)"], "sltiu $t0, $t2, 5").is_none());
+    assert!(nops(&["lwl $t0, 3($a0)"], "lwr $t0, 0($a0)").is_none());
+    assert!(nops(&["lwr $t0, 0($a0)"], "lwl $t0, 3($a0)").is_none());
+    assert!(nops(&["lwl $t0, 3($a0)"], "lwr $t1, 0($t0)") == Some(1));
+    assert!(nops(&["lwl $t0, 3($a0)"], "lwl $t0, 7($a0)") == Some(1));
+}
+
+#[test]
+fn delay_slots_in_reorder_mode() {
+    use crate::Functionality::Assembler::Assembler;
+
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".text 0x0\n.set reorder\nloop: addi $t0, $t0, -1\nbne $t1, $zero, loop\naddi $t1, $t1, 1\nbne $t1, $zero, loop\n.set noreorder\nj end\nend: nop",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(
+        program.code
+            == [
+                0x1520FFFF, 0x2108FFFF, // bne, with the addi moved into its slot
+                0x21290001, 0x1520FFFC, 0x00000000, // bne depends on the addi, nop inserted
+                0x08000006, 0x00000000,
+            ]
+    );
+    assert!(program.symbols["end"] == 0x18);
+    assert!(program.listing[2].ends_with("moved into the delay slot of line 4"));
+    assert!(program.listing[5].ends_with("nop inserted in the delay slot"));
+
+    // neither slti nor the addu standing between lw and its use can be moved past the branch
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".text 0x0\n.set reorder\nslti $t0, $t1, 5\nbeq $t0, $zero, end\nlw $t0, 0($a0)\naddu $t1, $t2, $t3\nbeq $t0, $zero, end\nend: nop",
+        )
+        .assemble()
+        .unwrap();
+    assert!(
+        program.code
+            == [
+                0x29280005, 0x11000005, 0x00000000, // slti stays before the beq
+                0x8C880000, 0x014B4821, 0x11000001, 0x00000000, 0x00000000,
+            ]
+    );
+}
+
+#[test]
+fn pipeline_hazards_are_reported_or_fixed() {
+    use crate::Functionality::Assembler::Assembler;
+
+    let src = "lw $t0, 0($sp)\naddi $t0, $t0, 1\nmflo $t1\nmult $t1, $t0\nend: nop";
+
+    let program = Assembler::new()
+        .add_source("test.s", &format!(".text 0x0\n{src}"))
+        .assemble()
+        .unwrap();
+    let lines: Vec<usize> = program
+        .warnings
+        .iter()
+        .map(|w| w.span.as_ref().unwrap().line)
+        .collect();
+    assert!(lines == [3, 5]);
+    assert!(program.symbols["end"] == 0x10);
+
+    let program = Assembler::new()
+        .add_source("test.s", &format!(".text 0x0\n.set reorder\n{src}"))
+        .assemble()
+        .unwrap();
+    assert!(program.warnings.is_empty());
+    assert!(program.code[1] == 0 && program.code[4] == 0 && program.code[5] == 0);
+    assert!(program.symbols["end"] == 0x1C);
+
+    // an unaligned word load and slti reading a different register need no nop
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".text 0x0\nlwl $t0, 3($a0)\nlwr $t0, 0($a0)\nslti $t1, $t2, 5\nlw $t3, 0($sp)\nslti $t0, $t1, 5",
+        )
+        .assemble()
+        .unwrap();
+    assert!(program.warnings.is_empty());
+}
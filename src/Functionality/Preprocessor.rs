@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use super::Conditional::Conditionals;
+use super::Include;
+use super::Macros::{head, tags_before, MacroProcessor, MAX_DEPTH};
+use super::TagResolution::SymbolTable;
+
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::MacroError;
+
+/**
+ *  Runs the stages that work on whole lines, before they are parsed: conditional blocks,
+ *  `.include` and macros. They run together in source order, so nothing in a skipped block
+ *  is included or expanded, and the lines that come out of an include or a macro go
+ *  through every stage again
+ */
+#[derive(Debug)]
+pub struct Preprocessor<'a> {
+    dirs: &'a [PathBuf],
+    macros: MacroProcessor,
+    conditionals: Conditionals,
+}
+
+impl<'a> Preprocessor<'a> {
+    /**
+     *  `.include`d files are searched for in `dirs`, conditions can use the constants
+     *  already in `symbols`
+     */
+    pub fn new(dirs: &'a [PathBuf], symbols: SymbolTable) -> Self {
+        Preprocessor {
+            dirs,
+            macros: MacroProcessor::default(),
+            conditionals: Conditionals::new(symbols),
+        }
+    }
+
+    /**
+     *  Preprocesses the lines of one source. Macros, constants and open conditional blocks
+     *  carry over to the sources that follow
+     */
+    pub fn source(&mut self, lines: Vec<Span>, errors: &mut Vec<Diagnostic>) -> Vec<Span> {
+        let mut stack: Vec<PathBuf> = lines
+            .first()
+            .map(|l| Include::identity(Path::new(&l.file)))
+            .into_iter()
+            .collect();
+        let mut out = Vec::new();
+        self.expand_into(lines, &mut stack, 0, &mut out, errors);
+        out
+    }
+
+    /**
+     *  Reports every conditional block still open at the end of the sources
+     */
+    pub fn finish(self) -> Vec<Diagnostic> {
+        self.conditionals.finish()
+    }
+
+    fn expand_into(
+        &mut self,
+        lines: Vec<Span>,
+        stack: &mut Vec<PathBuf>,
+        depth: usize,
+        out: &mut Vec<Span>,
+        errors: &mut Vec<Diagnostic>,
+    ) {
+        let mut lines = lines.into_iter();
+        while let Some(line) = lines.next() {
+            let line = match self.conditionals.filter(line, errors) {
+                Some(line) => line,
+                None => continue,
+            };
+            let (col, word) = match head(&line.line_text) {
+                Some((col, word)) => (col, word.to_ascii_lowercase()),
+                None => {
+                    out.push(line);
+                    continue;
+                }
+            };
+            match word.as_str() {
+                ".include" => {
+                    // tags in front of the directive point at the first included line
+                    out.extend(tags_before(&line, col));
+                    match Include::read(&line, col, self.dirs, stack) {
+                        Ok((path, included)) => {
+                            stack.push(path);
+                            self.expand_into(included, stack, depth, out, errors);
+                            stack.pop();
+                        }
+                        Err(e) => errors.push(e),
+                    }
+                }
+                ".macro" => {
+                    if let Err(e) = self.macros.define(&line, col, &mut lines) {
+                        errors.push(e);
+                    }
+                }
+                ".endm" => {
+                    errors.push(Diagnostic::new(&line.narrow(".endm"), MacroError::StrayEnd))
+                }
+                name if self.macros.is_macro(name) => {
+                    if depth >= MAX_DEPTH {
+                        errors.push(Diagnostic::new(&line, MacroError::TooDeep(word.clone())));
+                        continue;
+                    }
+                    match self.macros.invoke(&line, col, &word) {
+                        Ok(expanded) => self.expand_into(expanded, stack, depth + 1, out, errors),
+                        Err(e) => errors.push(e),
+                    }
+                }
+                _ => out.push(line),
+            }
+        }
+    }
+}
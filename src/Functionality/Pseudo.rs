@@ -0,0 +1,251 @@
+use crate::Functionality::TagResolution::SymbolTable;
+use crate::Structures::Diagnostic::{Diagnostic, Span};
+use crate::Structures::Errors::{CompileError, SyntaxError};
+use crate::Structures::Expression::Expr;
+use crate::Structures::Register::Register;
+use crate::Structures::Statement::{Operand, OperandKind};
+
+/**
+ *  A real instruction produced by expanding a pseudo-instruction
+ */
+pub type Expansion = (String, Vec<Operand>);
+
+const PSEUDO_INSTRUCTIONS: [&str; 12] = [
+    "li", "la", "move", "not", "neg", "b", "beqz", "bnez", "blt", "bgt", "ble", "bge",
+];
+
+pub fn is_pseudo(mnemonic: &str) -> bool {
+    PSEUDO_INSTRUCTIONS.contains(&mnemonic)
+}
+
+/**
+ *  Whether the expansion of `mnemonic` goes through the assembler temporary, `$at`
+ */
+pub fn uses_at(mnemonic: &str) -> bool {
+    matches!(mnemonic, "blt" | "bgt" | "ble" | "bge")
+}
+
+/**
+ *  Expands a pseudo-instruction into the real instructions that implement it. The number
+ *  of instructions only depends on the operands as written, so addresses can be assigned
+ *  right away
+ */
+pub fn expand(
+    mnemonic: &str,
+    operands: Vec<Operand>,
+    span: &Span,
+    symbols: &SymbolTable,
+) -> Result<Vec<Expansion>, Diagnostic> {
+    let reg = |r: Register| Operand {
+        kind: OperandKind::Register(r),
+        span: span.narrow(mnemonic),
+    };
+    let zero = reg(Register::ZERO);
+    let at = reg(Register::AT);
+    let instr = |m: &str, ops: Vec<Operand>| (m.to_string(), ops);
+
+    Ok(match mnemonic {
+        "move" => {
+            let [rd, rs] = take(operands, span)?;
+            vec![instr("addu", vec![rd, rs, zero])]
+        }
+        "not" => {
+            let [rd, rs] = take(operands, span)?;
+            vec![instr("nor", vec![rd, rs, zero])]
+        }
+        "neg" => {
+            let [rd, rs] = take(operands, span)?;
+            vec![instr("sub", vec![rd, zero, rs])]
+        }
+        "li" => {
+            let [rt, value] = take(operands, span)?;
+            load_immediate(rt, value, symbols)?
+        }
+        "la" => {
+            // same as `lui rt, %hi(label)` followed by `addiu rt, rt, %lo(label)`
+            let [rt, label] = take(operands, span)?;
+            let addr = match &label.kind {
+                OperandKind::Label(tag) => Expr::Symbol(tag.clone()),
+                OperandKind::Expr(e) => e.clone(),
+                OperandKind::Imm(v) => Expr::Num(*v),
+                _ => {
+                    return Err(Diagnostic::new(
+                        &label.span,
+                        SyntaxError::Expected("address".into()),
+                    ))
+                }
+            };
+            let hi = Operand {
+                kind: OperandKind::Hi(addr.clone()),
+                span: label.span.clone(),
+            };
+            let lo = Operand {
+                kind: OperandKind::Lo(addr),
+                span: label.span,
+            };
+            vec![
+                instr("lui", vec![rt.clone(), hi]),
+                instr("addiu", vec![rt.clone(), rt, lo]),
+            ]
+        }
+        "b" => {
+            let [label] = take(operands, span)?;
+            vec![instr("beq", vec![zero.clone(), zero, label])]
+        }
+        "beqz" => {
+            let [rs, label] = take(operands, span)?;
+            vec![instr("beq", vec![rs, zero, label])]
+        }
+        "bnez" => {
+            let [rs, label] = take(operands, span)?;
+            vec![instr("bne", vec![rs, zero, label])]
+        }
+        "blt" | "bgt" | "ble" | "bge" => {
+            // slt $at, .. then branch on $at. bgt and ble compare the other way around
+            let [rs, rt, label] = take(operands, span)?;
+            let (lhs, rhs) = match mnemonic {
+                "blt" | "bge" => (rs, rt),
+                _ => (rt, rs),
+            };
+            let branch = if matches!(mnemonic, "blt" | "bgt") {
+                "bne"
+            } else {
+                "beq"
+            };
+            vec![
+                instr("slt", vec![at.clone(), lhs, rhs]),
+                instr(branch, vec![at, zero, label]),
+            ]
+        }
+        _ => panic!("\"{mnemonic}\" is not a pseudo-instruction"),
+    })
+}
+
+/**
+ *  Picks the shortest sequence that loads `value`: a single addiu, ori or lui when the
+ *  constant allows it, lui followed by ori otherwise. The size has to be known right
+ *  away, so expressions may only refer to tags defined earlier
+ */
+fn load_immediate(
+    rt: Operand,
+    value: Operand,
+    symbols: &SymbolTable,
+) -> Result<Vec<Expansion>, Diagnostic> {
+    let v = match &value.kind {
+        OperandKind::Imm(v) => *v,
+        OperandKind::Label(name) if symbols.is_constant(name) => symbols
+            .value(name)
+            .map_err(|err| Diagnostic::new(&value.span, err))?,
+        OperandKind::Expr(e) => symbols
+            .evaluate(e)
+            .map_err(|err| Diagnostic::new(&value.span, err))?,
+        _ => {
+            return Err(Diagnostic::new(
+                &value.span,
+                SyntaxError::Expected("immediate".into()),
+            ))
+        }
+    };
+    if !(i32::MIN as i64..=u32::MAX as i64).contains(&v) {
+        return Err(Diagnostic::new(&value.span, CompileError::ImmSize));
+    }
+
+    let imm = |v: i64| Operand {
+        kind: OperandKind::Imm(v),
+        span: value.span.clone(),
+    };
+    let zero = Operand {
+        kind: OperandKind::Register(Register::ZERO),
+        span: value.span.clone(),
+    };
+    let bits = v as u32;
+    let (upper, lower) = ((bits >> 16) as i64, (bits & 0xFFFF) as i64);
+
+    Ok(if (-0x8000..=0x7FFF).contains(&v) {
+        vec![("addiu".into(), vec![rt, zero, imm(v)])]
+    } else if (0..=0xFFFF).contains(&v) {
+        vec![("ori".into(), vec![rt, zero, imm(v)])]
+    } else if lower == 0 {
+        vec![("lui".into(), vec![rt, imm(upper)])]
+    } else {
+        vec![
+            ("lui".into(), vec![rt.clone(), imm(upper)]),
+            ("ori".into(), vec![rt.clone(), rt, imm(lower)]),
+        ]
+    })
+}
+
+/**
+ *  Takes exactly `N` operands, reporting a missing or extra one otherwise
+ */
+fn take<const N: usize>(operands: Vec<Operand>, span: &Span) -> Result<[Operand; N], Diagnostic> {
+    if operands.len() > N {
+        let extra = &operands[N];
+        return Err(Diagnostic::new(
+            &extra.span,
+            SyntaxError::ExtraOperand(extra.span.text().into()),
+        ));
+    }
+    operands.try_into().map_err(|_| {
+        let end = Span {
+            col_start: span.col_end,
+            col_end: span.col_end + 1,
+            ..span.clone()
+        };
+        Diagnostic::new(&end, SyntaxError::MissingOperand)
+    })
+}
+
+#[test]
+fn li_picks_the_shortest_expansion() {
+    let mnemonics = |line: &str| -> Vec<String> {
+        let (statements, _) = crate::Functionality::Parser::parse(line, "test.s");
+        match &statements[0].kind {
+            crate::Structures::Statement::StatementKind::Instruction(m, ops) => {
+                expand(m, ops.clone(), &statements[0].span, &SymbolTable::new())
+                    .unwrap()
+                    .into_iter()
+                    .map(|(m, _)| m)
+                    .collect()
+            }
+            _ => panic!(),
+        }
+    };
+
+    assert!(mnemonics("li $t0, -5") == ["addiu"]);
+    assert!(mnemonics("li $t0, 0xFFFF") == ["ori"]);
+    assert!(mnemonics("li $t0, 0x10000") == ["lui"]);
+    assert!(mnemonics("li $t0, 0x12345678") == ["lui", "ori"]);
+    assert!(mnemonics("li $t0, -0x10000") == ["lui"]);
+    assert!(mnemonics("blt $t0, $t1, end") == ["slt", "bne"]);
+}
+
+#[test]
+fn pseudo_instructions() {
+    use crate::Functionality::Assembler::Assembler;
+
+    let program = Assembler::new()
+        .add_source(
+            "test.s",
+            ".data 0x10008000\nval: .word 7\n.text 0x0\nli $t0, 0x12345678\nli $t1, -1\nla $t2, val\nmove $a0, $t0\nblt $t0, $t1, end\nnot $t3, $t0\nend: beqz $t0, end",
+        )
+        .assemble()
+        .unwrap();
+
+    assert!(
+        program.code
+            == [
+                0x3C081234, 0x35085678, // li $t0, 0x12345678
+                0x2409FFFF, // li $t1, -1
+                0x3C0A1001, 0x254A8000, // la $t2, val
+                0x01002021, // move
+                0x0109082A, 0x14200001, // blt
+                0x01005827, // not
+                0x1100FFFF, // beqz
+            ]
+    );
+    assert!(program.symbols["end"] == 0x24);
+    // the listing shows the instructions a pseudo-instruction was expanded into
+    assert!(program.listing[2].ends_with("@ [0x00000004]  ori $t0, $t0, 0x5678"));
+    assert!(program.listing[10].ends_with("beq $t0, $zero, 0x00000024"));
+}
@@ -1,69 +1,166 @@
-use mut_static::MutStatic;
 use std::collections::HashMap;
 
 use crate::Structures::Errors::CompileError;
+use crate::Structures::Expression::Expr;
 
+/**
+ *  A number or address an operand refers to, known once every tag has an address
+ */
 #[derive(Debug, Clone)]
 pub enum Tag {
     Imm(u32, bool),
     BuildPending(String),
     Resolved(u32),
+    Constant(i64), // defined with .equ, .set or .eqv
+    Expr(Expr),
 }
 
-lazy_static! {
-    #[derive(Debug)]
-    pub static ref TAGDICT: MutStatic<HashMap<String, Tag>> = MutStatic::new();
+/**
+ *  Value of an immediate field: a number or address, or one of the halves of one
+ */
+#[derive(Debug, Clone)]
+pub enum Immediate {
+    Value(Tag),
+    Hi(Expr),
+    Lo(Expr),
 }
 
-pub fn init() {
-    //singleton pattern
-    if TAGDICT.read().is_ok() {
-        return;
-    }
-    TAGDICT.set(HashMap::new()).unwrap();
-    TAGDICT
-        .write()
-        .unwrap()
-        .insert(".text".into(), Tag::BuildPending(".text".into()));
-    TAGDICT
-        .write()
-        .unwrap()
-        .insert(".data".into(), Tag::BuildPending(".data".into()));
+/**
+ *  What the symbol table knows about a name
+ */
+#[derive(Debug, Clone)]
+enum Symbol {
+    Pending, // used, but not defined yet
+    Address(u32),
+    Constant(i64),
 }
 
-pub fn log_or_resolve(tag: &str) -> Tag {
-    if TAGDICT.read().unwrap().contains_key(tag) {
-        let entry = &TAGDICT.read().unwrap()[tag];
-        if let Tag::Resolved(v) = entry {
-            Tag::Resolved(*v)
-        } else if let Tag::BuildPending(t) = entry {
-            Tag::BuildPending(t.clone())
-        } else {
-            panic!()
+impl Tag {
+    /**
+     *  Value of the operand, with the tags and constants in `symbols`
+     */
+    pub fn value(self, symbols: &SymbolTable) -> Result<i64, CompileError> {
+        match self {
+            Tag::Imm(v, true) => Ok(v as i32 as i64),
+            Tag::Imm(v, false) => Ok(v as i64),
+            Tag::BuildPending(s) => symbols.value(&s),
+            Tag::Resolved(v) => Ok(v as i64),
+            Tag::Constant(v) => Ok(v),
+            Tag::Expr(e) => symbols.evaluate(&e),
         }
-    } else {
-        TAGDICT
-            .write()
-            .unwrap()
-            .insert(tag.to_string(), Tag::BuildPending(tag.to_string()));
-        Tag::BuildPending(tag.to_string())
     }
 }
 
-pub fn log_addr(tag: String, addr: u32) {
-    TAGDICT.write().unwrap().insert(tag, Tag::Resolved(addr));
+/**
+ *  Labels seen during a single assembly run. Each run owns its table, so
+ *  independent assemblies never see each other's labels
+ */
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    tags: HashMap<String, Symbol>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        let mut tags = HashMap::new();
+        tags.insert(".text".into(), Symbol::Pending);
+        tags.insert(".data".into(), Symbol::Pending);
+        SymbolTable { tags }
+    }
 }
 
-pub fn resolve(tag: String) -> Result<u32, CompileError> {
-    if let Tag::Resolved(addr) = TAGDICT.read().unwrap()[&tag] {
-        Ok(addr)
-    } else {
-        Err(CompileError::TagResolution(tag))
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    pub fn log_or_resolve(&mut self, tag: &str) -> Tag {
+        match self.tags.get(tag) {
+            Some(Symbol::Address(v)) => Tag::Resolved(*v),
+            Some(Symbol::Constant(v)) => Tag::Imm(*v as u32, *v < 0),
+            Some(Symbol::Pending) => Tag::BuildPending(tag.to_string()),
+            None => {
+                self.tags.insert(tag.to_string(), Symbol::Pending);
+                Tag::BuildPending(tag.to_string())
+            }
+        }
+    }
+
+    /**
+     *  Gives `tag` the address `addr`. A tag can only be defined once
+     */
+    pub fn log_addr(&mut self, tag: String, addr: u32) -> Result<(), CompileError> {
+        if self.is_defined(&tag) {
+            return Err(CompileError::Redefinition(tag));
+        }
+        self.tags.insert(tag, Symbol::Address(addr));
+        Ok(())
+    }
+
+    /**
+     *  Defines a symbolic constant. Constants cannot be redefined, nor share a name with a tag
+     */
+    pub fn define(&mut self, name: &str, value: i64) -> Result<(), CompileError> {
+        if self.is_defined(name) {
+            return Err(CompileError::Redefinition(name.into()));
+        }
+        self.tags.insert(name.to_string(), Symbol::Constant(value));
+        Ok(())
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        matches!(
+            self.tags.get(name),
+            Some(Symbol::Address(_) | Symbol::Constant(_))
+        )
+    }
+
+    pub fn is_constant(&self, name: &str) -> bool {
+        matches!(self.tags.get(name), Some(Symbol::Constant(_)))
+    }
+
+    /**
+     *  Value of a tag or constant
+     */
+    pub fn value(&self, name: &str) -> Result<i64, CompileError> {
+        match self.tags.get(name) {
+            Some(Symbol::Address(addr)) => Ok(*addr as i64),
+            Some(Symbol::Constant(v)) => Ok(*v),
+            _ => Err(CompileError::TagResolution(name.into())),
+        }
+    }
+
+    /**
+     *  Evaluates `expr` with the addresses known so far
+     */
+    pub fn evaluate(&self, expr: &Expr) -> Result<i64, CompileError> {
+        expr.eval(&|tag| self.value(tag))
+    }
+
+    /**
+     *  Returns a snapshot of every tag that has been resolved to an address
+     */
+    pub fn resolved(&self) -> HashMap<String, u32> {
+        self.tags
+            .iter()
+            .filter_map(|(k, v)| match v {
+                Symbol::Address(addr) => Some((k.clone(), *addr)),
+                _ => None,
+            })
+            .collect()
     }
 }
 
 #[test]
-fn single_tagdict_instance_safe() {
-    init();
-    init();
+fn symbol_tables_are_independent() {
+    let mut a = SymbolTable::new();
+    let mut b = SymbolTable::new();
+    a.log_addr("loop".into(), 0x10).unwrap();
+    b.log_addr("loop".into(), 0x20).unwrap();
+
+    assert!(a.value("loop").unwrap() == 0x10);
+    assert!(b.value("loop").unwrap() == 0x20);
+    assert!(SymbolTable::new().value("loop").is_err());
+    assert!(a.log_addr("loop".into(), 0x30).is_err());
+    assert!(a.value("loop").unwrap() == 0x10);
 }
@@ -1,3 +1,12 @@
+pub mod Assembler;
 pub mod CodeGen;
-pub mod Preprocess;
+pub mod Conditional;
+pub mod Include;
+pub mod IsaExtensions;
+pub mod Lexer;
+pub mod Macros;
+pub mod Parser;
+pub mod Pipeline;
+pub mod Preprocessor;
+pub mod Pseudo;
 pub mod TagResolution;
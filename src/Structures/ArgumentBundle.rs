@@ -1,20 +1,26 @@
 use super::{
-    Instruction::InstructionType,
-    Opcodes::OPCODES::{I, J, R},
+    Diagnostic::{Diagnostic, Span},
+    Errors::{CompileError, SyntaxError},
+    InstructionTable::{Field, InstrDef},
+    Register::{CopRegister, Register},
+    Statement::{Operand, OperandKind},
 };
 
-use crate::Functionality::TagResolution;
-use crate::Functionality::TagResolution::Tag;
+use crate::Functionality::TagResolution::{Immediate, SymbolTable, Tag};
 
 use std::collections::VecDeque;
 
 #[derive(Debug)]
 pub struct ArgumentBundle {
-    pub rs: u32,
-    pub rt: u32,
-    pub rd: u32,
+    pub rs: Register,
+    pub rt: Register,
+    pub rd: Register,
     pub sham: u32,
-    pub imm: Tag, //for jump instructions, imm is used as jtarg
+    pub imm: Immediate,
+    pub target: Tag,     //branch or jump target
+    pub fs: CopRegister, //coprocessor registers: fs is encoded in the rd field,
+    pub ft: CopRegister, //ft in the rt field
+    pub fd: CopRegister, //and fd in the sham field
 }
 
 impl Default for ArgumentBundle {
@@ -24,187 +30,240 @@ impl Default for ArgumentBundle {
             rt: Default::default(),
             rd: Default::default(),
             sham: Default::default(),
-            imm: Tag::Imm(0, false),
+            imm: Immediate::Value(Tag::Imm(0, false)),
+            target: Tag::Imm(0, false),
+            fs: Default::default(),
+            ft: Default::default(),
+            fd: Default::default(),
         }
     }
 }
 
 impl ArgumentBundle {
-    pub fn construct(arg_vec: VecDeque<&str>, itype: &InstructionType, func: u32) -> Self {
-        match itype {
-            InstructionType::I => ArgumentBundle::construct_I(arg_vec, func),
-            InstructionType::R => ArgumentBundle::construct_R(arg_vec, func),
-            InstructionType::J => ArgumentBundle::construct_J(arg_vec, func),
-            InstructionType::Special => {
-                panic!("A special-type instruction reached ArgumentBundle construct");
-            }
-        }
-    }
+    /**
+     *  Fills in the fields named by the operand pattern of `def`, in order
+     */
+    pub fn construct(
+        mut arg_vec: VecDeque<Operand>,
+        def: &InstrDef,
+        span: &Span,
+        symbols: &mut SymbolTable,
+    ) -> Result<Self, Diagnostic> {
+        let mut args = ArgumentBundle::default();
+        let (double_dest, double_src) = doubles(&def.mnemonic);
 
-    fn construct_R(mut arg_vec: VecDeque<&str>, func: u32) -> Self {
-        // rd first except in mult, div and jr where it is rs
-        let rd: u32;
-        let rs: u32;
-        let rt: u32;
-        let sham: u32;
-        match func {
-            R::MULT | R::MULTU | R::DIV | R::DIVU | R::JR => {
-                // <op> rs ..
-                rs = arg_vec.pop_front().unwrap().parse().unwrap();
-
-                //then if it is JR -> we're done
-                //else the second is rt
-                if func != R::JR {
-                    rt = arg_vec.pop_front().unwrap().parse().unwrap()
-                } else {
-                    rt = 0
+        for field in &def.operands {
+            match field {
+                Field::Rs => args.rs = next_reg(&mut arg_vec, span)?,
+                Field::Rt => args.rt = next_reg(&mut arg_vec, span)?,
+                Field::Rd => args.rd = next_reg(&mut arg_vec, span)?,
+                Field::RdRt => {
+                    args.rd = next_reg(&mut arg_vec, span)?;
+                    args.rt = args.rd;
                 }
-
-                ArgumentBundle {
-                    rs,
-                    rt,
-                    rd: 0,
-                    sham: 0,
-                    imm: Tag::Imm(0, false),
+                Field::Sa => args.sham = next_sham(&mut arg_vec, span, symbols)?,
+                Field::Imm => args.imm = next_imm(&mut arg_vec, span, symbols)?,
+                Field::Label | Field::Target => {
+                    args.target = next_target(&mut arg_vec, span, symbols)?
                 }
-            }
-            _ => {
-                // <op> rd rs ...
-                rd = arg_vec.pop_front().unwrap().parse().unwrap();
-                rs = arg_vec.pop_front().unwrap().parse().unwrap();
-
-                if func == R::MFHI || func == R::MFLO {
-                    return ArgumentBundle {
-                        rs,
-                        rt: 0,
-                        rd,
-                        sham: 0,
-                        imm: Tag::Imm(0, false),
-                    };
-                };
-
-                //rt at the end for all except shifts
-
-                match func {
-                    R::SLL | R::SRA | R::SRAV | R::SRLV => {
-                        sham = arg_vec.pop_front().unwrap().parse().unwrap();
-                        rt = 0;
-                    }
-
-                    _ => {
-                        sham = 0;
-                        rt = arg_vec.pop_front().unwrap().parse().unwrap();
+                Field::Mem => {
+                    let op = next(&mut arg_vec, span)?;
+                    match op.kind {
+                        OperandKind::Memory(offset, base) => {
+                            args.rs = base;
+                            args.imm = imm_value(*offset, &op.span, symbols)?;
+                        }
+                        _ => {
+                            return Err(Diagnostic::new(
+                                &op.span,
+                                SyntaxError::Expected("offset(base)".into()),
+                            ))
+                        }
                     }
                 }
-
-                ArgumentBundle {
-                    rs,
-                    rt,
-                    rd,
-                    sham,
-                    imm: Tag::Imm(0, false),
-                }
-            }
-        }
-    }
-
-    fn construct_I(mut arg_vec: VecDeque<&str>, func: u32) -> Self {
-        //tags are assumed to have been converted to the actual jump target
-
-        let rs;
-        let rt;
-        let imm;
-        //dbg!(&arg_vec);
-
-        match func {
-            I::BGTZ | I::BLEZ => {
-                //conditional jumps: <op> rs, imm
-                rs = arg_vec.pop_front().unwrap().parse().unwrap();
-
-                let imm_candidate = arg_vec.pop_front().unwrap();
-                imm = if imm_candidate
-                    .matches(char::is_numeric)
-                    .collect::<String>()
-                    .len()
-                    == imm_candidate.len()
-                {
-                    //all characters were numbers, it is a proper imm
-                    let imm: i32 = imm_candidate.parse().unwrap();
-                    Tag::Imm(imm as u32, imm < 0)
-                } else {
-                    TagResolution::log_or_resolve(imm_candidate)
-                };
-                rt = 0;
-            }
-            I::BNE | I::BEQ => {
-                //bne: <bne> rs rt imm
-                rs = arg_vec.pop_front().unwrap().parse().unwrap();
-                rt = arg_vec.pop_front().unwrap().parse().unwrap();
-                let imm_candidate = arg_vec.pop_front().unwrap();
-                imm = if imm_candidate
-                    .matches(char::is_numeric)
-                    .collect::<String>()
-                    .len()
-                    == imm_candidate.len()
-                {
-                    //all characters were numbers, it is a proper imm
-                    let imm: i32 = imm_candidate.parse().unwrap();
-                    Tag::Imm(imm as u32, imm < 0)
-                } else {
-                    TagResolution::log_or_resolve(imm_candidate)
-                };
-            }
-            _ => {
-                // other: <op> rt, rs, imm
-                rt = arg_vec.pop_front().unwrap().parse().unwrap();
-                rs = arg_vec.pop_front().unwrap().parse().unwrap();
-                let imm_candidate: String = arg_vec.pop_front().unwrap().to_string();
-                imm = if imm_candidate.starts_with("0X") || imm_candidate.starts_with("0x") {
-                    let imm = i32::from_str_radix(
-                        imm_candidate
-                            .trim_start_matches("0x")
-                            .trim_start_matches("0X"),
-                        16,
-                    )
-                    .unwrap();
-                    Tag::Imm(imm as u32, imm < 0)
-                } else {
-                    let imm: i32 = imm_candidate.parse().unwrap();
-                    Tag::Imm(imm as u32, imm < 0)
-                };
+                Field::Fs => args.fs = next_fp_reg(&mut arg_vec, span, double_src)?,
+                Field::Ft => args.ft = next_fp_reg(&mut arg_vec, span, double_src)?,
+                Field::Fd => args.fd = next_fp_reg(&mut arg_vec, span, double_dest)?,
+                Field::Cs => args.fs = next_cop_reg(&mut arg_vec, span)?,
             }
         }
 
-        ArgumentBundle {
-            rs,
-            rt,
-            rd: 0,
-            sham: 0,
-            imm,
+        match arg_vec.pop_front() {
+            Some(extra) => Err(Diagnostic::new(
+                &extra.span,
+                SyntaxError::ExtraOperand(extra.span.text().into()),
+            )),
+            None => Ok(args),
         }
     }
+}
 
-    fn construct_J(mut arg_vec: VecDeque<&str>, _func: u32) -> Self {
-        let imm_candidate = arg_vec.pop_front().unwrap();
-        let imm = if imm_candidate
-            .matches(char::is_numeric)
-            .collect::<String>()
-            .len()
-            == imm_candidate.len()
-        {
-            //all characters were numbers, it is a proper imm
-            let imm: i32 = imm_candidate.parse().unwrap();
-            Tag::Imm(imm as u32, imm < 0)
-        } else {
-            TagResolution::log_or_resolve(imm_candidate)
+/**
+ *  Pops the next operand, failing if the instruction ran out of them
+ */
+fn next(arg_vec: &mut VecDeque<Operand>, span: &Span) -> Result<Operand, Diagnostic> {
+    arg_vec.pop_front().ok_or_else(|| {
+        let end = Span {
+            col_start: span.col_end,
+            col_end: span.col_end + 1,
+            ..span.clone()
         };
+        Diagnostic::new(&end, SyntaxError::MissingOperand)
+    })
+}
+
+fn next_reg(arg_vec: &mut VecDeque<Operand>, span: &Span) -> Result<Register, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    match op.kind {
+        OperandKind::Register(r) => Ok(r),
+        _ => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("register".into()),
+        )),
+    }
+}
 
-        ArgumentBundle {
-            rs: 0,
-            rt: 0,
-            rd: 0,
-            sham: 0,
-            imm,
+/**
+ *  Pops a coprocessor register, given either by name or by number
+ */
+fn next_cop_reg(arg_vec: &mut VecDeque<Operand>, span: &Span) -> Result<CopRegister, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    match op.kind {
+        OperandKind::CopRegister(r) => Ok(r),
+        OperandKind::Register(r) => Ok(CopRegister::from_number(r)),
+        _ => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("coprocessor register".into()),
+        )),
+    }
+}
+
+/**
+ *  Which floating point operands hold doubles, the destination and the sources. `cvt.<to>.<from>`
+ *  converts between formats, every other `.d` operation works on doubles only
+ */
+fn doubles(mnemonic: &str) -> (bool, bool) {
+    match mnemonic.split('.').collect::<Vec<&str>>().as_slice() {
+        ["cvt", to, from] => (*to == "d", *from == "d"),
+        [_, .., "d"] => (true, true),
+        _ => (false, false),
+    }
+}
+
+/**
+ *  Pops a floating point register. A double takes an even/odd register pair on the R3010
+ *  and is named by its even register
+ */
+fn next_fp_reg(
+    arg_vec: &mut VecDeque<Operand>,
+    span: &Span,
+    double: bool,
+) -> Result<CopRegister, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    match op.kind {
+        OperandKind::FpRegister(r) if double && r.number() % 2 != 0 => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::OddFpRegister(r.number()),
+        )),
+        OperandKind::FpRegister(r) => Ok(r),
+        _ => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("floating point register".into()),
+        )),
+    }
+}
+
+/**
+ *  Pops a shift amount. Constants have to be defined before they are used here
+ */
+fn next_sham(
+    arg_vec: &mut VecDeque<Operand>,
+    span: &Span,
+    symbols: &SymbolTable,
+) -> Result<u32, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    let v = match op.kind {
+        OperandKind::Imm(v) => v,
+        OperandKind::Label(tag) => symbols
+            .value(&tag)
+            .map_err(|e| Diagnostic::new(&op.span, e))?,
+        OperandKind::Expr(e) => symbols
+            .evaluate(&e)
+            .map_err(|e| Diagnostic::new(&op.span, e))?,
+        _ => {
+            return Err(Diagnostic::new(
+                &op.span,
+                SyntaxError::Expected("shift amount".into()),
+            ))
         }
+    };
+    if !(0..32).contains(&v) {
+        return Err(Diagnostic::new(&op.span, SyntaxError::ValueTooLarge(v, 5)));
+    }
+    Ok(v as u32)
+}
+
+/**
+ *  Pops a branch or jump target, which is either a number or a tag. A constant is taken
+ *  as an address like a tag, whether or not it has been defined yet
+ */
+fn next_target(
+    arg_vec: &mut VecDeque<Operand>,
+    span: &Span,
+    symbols: &mut SymbolTable,
+) -> Result<Tag, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    match op.kind {
+        OperandKind::Imm(v) => imm_tag(v, &op.span),
+        OperandKind::Label(tag) if symbols.is_constant(&tag) => symbols
+            .value(&tag)
+            .map(Tag::Constant)
+            .map_err(|e| Diagnostic::new(&op.span, e)),
+        OperandKind::Label(tag) => Ok(symbols.log_or_resolve(&tag)),
+        OperandKind::Expr(e) => Ok(Tag::Expr(e)),
+        _ => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("tag or offset".into()),
+        )),
+    }
+}
+
+fn next_imm(
+    arg_vec: &mut VecDeque<Operand>,
+    span: &Span,
+    symbols: &mut SymbolTable,
+) -> Result<Immediate, Diagnostic> {
+    let op = next(arg_vec, span)?;
+    imm_value(op.kind, &op.span, symbols)
+}
+
+/**
+ *  An immediate field: a number, the address of a tag, or one of its halves
+ */
+fn imm_value(
+    kind: OperandKind,
+    span: &Span,
+    symbols: &mut SymbolTable,
+) -> Result<Immediate, Diagnostic> {
+    match kind {
+        OperandKind::Imm(v) => imm_tag(v, span).map(Immediate::Value),
+        OperandKind::Label(tag) => Ok(Immediate::Value(symbols.log_or_resolve(&tag))),
+        OperandKind::Expr(e) => Ok(Immediate::Value(Tag::Expr(e))),
+        OperandKind::Hi(e) => Ok(Immediate::Hi(e)),
+        OperandKind::Lo(e) => Ok(Immediate::Lo(e)),
+        _ => Err(Diagnostic::new(
+            span,
+            SyntaxError::Expected("immediate".into()),
+        )),
+    }
+}
+
+/**
+ *  Immediates are kept as their 32b pattern plus a sign flag, anything wider is rejected here
+ */
+fn imm_tag(v: i64, span: &Span) -> Result<Tag, Diagnostic> {
+    if !(i32::MIN as i64..=u32::MAX as i64).contains(&v) {
+        return Err(Diagnostic::new(span, CompileError::ImmSize));
     }
+    Ok(Tag::Imm(v as u32, v < 0))
 }
@@ -1,5 +1,11 @@
-use super::Errors::SyntaxError;
-use crate::{CodeGen, TagResolution};
+use super::Diagnostic::{Diagnostic, Span};
+use super::Errors::{MetadataError, SyntaxError};
+use super::Expression::Expr;
+use super::Statement::{Operand, OperandKind};
+use crate::Functionality::TagResolution::SymbolTable;
+
+#[cfg(test)]
+use crate::Functionality::Lexer::parse_int_hex_or_dec;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum DType {
@@ -16,177 +22,271 @@ pub struct Data {
     pub contents: Vec<u8>,
     pub dt: DType,
     pub tagname: String,
+    pub fixups: Vec<Fixup>,
 }
 
-impl From<String> for Data {
-    fn from(st: String) -> Self {
-        let mut s: &str = &st;
-        // Format: "<tag>: .<data_type> <contents>"
-        // This string must be manually split since <contents> could be a string containing any characters we set as delimiters
-
-        let tagname = if let Some(tag_idx) = s.find(':') {
-            let res = s[..tag_idx].trim();
-            s = &s[tag_idx + 1..].trim();
-            res.to_string()
-        } else {
-            panic!("Syntax Error")
-        };
-
-        let dt_pre = if let Some(dt_end_idx) = s.find(' ') {
-            let res = s[1..dt_end_idx].trim();
-            s = &s[dt_end_idx..].trim();
-            res
-        } else {
-            panic!("Syntax Error")
-        };
-
-        let content_pre = s.trim();
+/**
+ *  An element that refers to tags, and is patched into `contents` once every tag
+ *  has an address
+ */
+#[derive(Debug)]
+pub struct Fixup {
+    pub offset: usize,
+    pub size: u32,
+    pub expr: Expr,
+    pub span: Span,
+}
 
-        let (dt, contents) = match dt_pre {
-            "word" => {
-                let mut content: Vec<u8> = Vec::new();
-                for element in content_pre.to_string().replace(" ", "").split(",") {
-                    content.append(&mut to_size_N::<4>(parse_int_hex_or_dec(element)));
-                }
-                (DType::Word, content)
-            }
-            "half" => {
-                let mut content: Vec<u8> = Vec::new();
-                for element in content_pre.to_string().replace(" ", "").split(",") {
-                    content.append(&mut to_size_N::<2>(parse_int_hex_or_dec(element)));
-                }
-                (DType::Half, content)
-            }
-            "byte" => {
-                let mut content: Vec<u8> = Vec::new();
-                for element in content_pre.to_string().replace(" ", "").split(",") {
-                    content.append(&mut to_size_N::<1>(parse_int_hex_or_dec(element)));
-                }
-                (DType::Byte, content)
-            }
-            "asciiz" => {
-                let st = match delimit_str(content_pre) {
-                    Ok(s) => s,
-                    Err(eobj) => panic!("{}", eobj),
-                };
-                let mut zterm: Vec<u8> = st.as_bytes().to_vec();
-                zterm.push('\0' as u8);
+impl Data {
+    /**
+     *  Builds the contents of a data directive. `tagname` names the data in diagnostics.
+     *  Sizes are needed right away, so they can only refer to tags already in `symbols`.
+     *  `room` is what is left of the address space, no `.space` may take more than that
+     */
+    pub fn new(
+        directive: &str,
+        args: &[Operand],
+        tagname: String,
+        span: &Span,
+        symbols: &SymbolTable,
+        room: u64,
+    ) -> Result<Self, Diagnostic> {
+        let mut fixups = Vec::new();
+        let (dt, contents) = match directive {
+            ".word" => (DType::Word, parse_elements::<4>(args, span, &mut fixups)?),
+            ".half" => (DType::Half, parse_elements::<2>(args, span, &mut fixups)?),
+            ".byte" => (DType::Byte, parse_elements::<1>(args, span, &mut fixups)?),
+            ".asciiz" => {
+                let mut zterm: Vec<u8> = single_string(args, span)?.as_bytes().to_vec();
+                zterm.push(b'\0');
 
                 (DType::ZTerminatedString, zterm)
             }
-            "ascii" => {
-                let st = match delimit_str(content_pre) {
-                    Ok(s) => s,
-                    Err(eobj) => panic!("{}", eobj),
-                };
-
-                (DType::String, st.as_bytes().to_vec())
-            }
-            "space" => (
-                DType::Space,
-                vec![0u8; parse_int_hex_or_dec(content_pre) as usize],
+            ".ascii" => (
+                DType::String,
+                single_string(args, span)?.as_bytes().to_vec(),
             ),
-            unk => panic!("Unknown data type {}", unk),
+            ".space" => match args {
+                [op] => {
+                    let n = match &op.kind {
+                        OperandKind::Imm(n) => Ok(*n),
+                        OperandKind::Label(tag) => symbols.value(tag),
+                        OperandKind::Expr(e) => symbols.evaluate(e),
+                        _ => {
+                            return Err(Diagnostic::new(
+                                &op.span,
+                                SyntaxError::Expected("size".into()),
+                            ))
+                        }
+                    }
+                    .map_err(|e| Diagnostic::new(&op.span, e))?;
+                    if n < 0 {
+                        return Err(Diagnostic::new(
+                            &op.span,
+                            SyntaxError::Expected("size".into()),
+                        ));
+                    }
+                    if n as u64 > room {
+                        return Err(Diagnostic::new(&op.span, MetadataError::SegmentOverflow));
+                    }
+                    (DType::Space, vec![0u8; n as usize])
+                }
+                _ => return Err(Diagnostic::new(span, SyntaxError::MissingOperand)),
+            },
+            _ => unreachable!("only called for directives that pass is_data_directive"),
         };
 
-        Data {
+        Ok(Data {
             contents,
             dt,
             tagname,
+            fixups,
+        })
+    }
+
+    /**
+     *  Writes the value of a fixup over its placeholder bytes
+     */
+    pub fn patch(&mut self, fixup: &Fixup, v: i64) -> Result<(), SyntaxError> {
+        let bytes = match fixup.size {
+            1 => fit::<1>(v).and_then(to_size_N::<1>),
+            2 => fit::<2>(v).and_then(to_size_N::<2>),
+            _ => fit::<4>(v).and_then(to_size_N::<4>),
+        }?;
+        self.contents[fixup.offset..fixup.offset + bytes.len()].copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/**
+ *  Whether `directive` emits data
+ */
+pub fn is_data_directive(directive: &str) -> bool {
+    matches!(
+        directive,
+        ".word" | ".half" | ".byte" | ".asciiz" | ".ascii" | ".space"
+    )
+}
+
+/**
+ *  Packs a list of integers into N bytes each. Elements referring to tags are left as
+ *  zeros and logged in `fixups`
+ */
+fn parse_elements<const N: u32>(
+    args: &[Operand],
+    span: &Span,
+    fixups: &mut Vec<Fixup>,
+) -> Result<Vec<u8>, Diagnostic> {
+    if args.is_empty() {
+        return Err(Diagnostic::new(span, SyntaxError::MissingOperand));
+    }
+    let mut content: Vec<u8> = Vec::new();
+    for element in args {
+        let expr = match &element.kind {
+            OperandKind::Label(tag) => Some(Expr::Symbol(tag.clone())),
+            OperandKind::Expr(e) => Some(e.clone()),
+            _ => None,
+        };
+        if let Some(expr) = expr {
+            fixups.push(Fixup {
+                offset: content.len(),
+                size: N,
+                expr,
+                span: element.span.clone(),
+            });
+            content.resize(content.len() + N as usize, 0);
+            continue;
         }
+        let mut bytes = match element.kind {
+            OperandKind::Imm(v) => fit::<N>(v).and_then(to_size_N::<N>),
+            _ => Err(SyntaxError::Expected("integer".into())),
+        }
+        .map_err(|e| Diagnostic::new(&element.span, e))?;
+        content.append(&mut bytes);
+    }
+    Ok(content)
+}
+
+fn single_string<'a>(args: &'a [Operand], span: &Span) -> Result<&'a str, Diagnostic> {
+    match args {
+        [Operand {
+            kind: OperandKind::Str(s),
+            ..
+        }] => Ok(s),
+        [op, ..] => Err(Diagnostic::new(
+            &op.span,
+            SyntaxError::Expected("a single string".into()),
+        )),
+        [] => Err(Diagnostic::new(span, SyntaxError::MissingOperand)),
     }
 }
 
-impl From<&str> for Data {
-    fn from(s: &str) -> Self {
-        s.to_string().into()
+/**
+ *  Values may be given signed or unsigned as long as they fit in N bytes
+ */
+fn fit<const N: u32>(v: i64) -> Result<u32, SyntaxError> {
+    let bits = N * 8;
+    if v < -(1i64 << (bits - 1)) || v >= (1i64 << bits) {
+        return Err(SyntaxError::ValueTooLarge(v, N));
     }
+    Ok((v as u32) & (u32::MAX >> (32 - bits)))
 }
 
-fn to_size_N<const N: u32>(n_in: u32) -> Vec<u8> {
-    assert!(
-        (n_in as usize) < 2_usize.pow(N * 8),
-        "Given number is too large for proposed size"
-    );
+fn to_size_N<const N: u32>(n_in: u32) -> Result<Vec<u8>, SyntaxError> {
+    if (n_in as usize) >= 2_usize.pow(N * 8) {
+        return Err(SyntaxError::ValueTooLarge(n_in as i64, N));
+    }
     let mut n = n_in;
     let mask_b = 0x000000ff;
     let mut v: Vec<u8> = Vec::new();
 
     for _ in 0..N {
-        let e = (n & mask_b).try_into().expect(&format!(
-            "Internal error: masked element in position {} of {} of did not fit in 8b",
-            N, n
-        )); //n_in is always 32b, keep first 8b
+        let e = (n & mask_b).try_into().unwrap_or_else(|_| {
+            panic!(
+                "Internal error: masked element in position {} of {} of did not fit in 8b",
+                N, n
+            )
+        }); //n_in is always 32b, keep first 8b
         v.push(e);
-        n = n >> 8;
+        n >>= 8;
     }
 
     v.reverse();
-    v
+    Ok(v)
 }
 
-/**
- *  Converts a string representation of an integer into an actual integer
- */
-fn parse_int_hex_or_dec(s: &str) -> u32 {
-    if s.starts_with("0X") || s.starts_with("0x") {
-        u32::from_str_radix(s.trim_start_matches("0x").trim_start_matches("0X"), 16)
-            .expect("Given string \"{}\" does not represent a valid base 16 integer")
-    } else {
-        s.parse().expect(&format!(
-            "Given string \"{}\" does not represent a valid base 10 integer",
-            s
-        ))
-    }
-}
-
-/**
- *  Takes a string containing quotes and returns Result with quotes removed
- */
-fn delimit_str(st: &str) -> Result<&str, SyntaxError> {
-    if let Some(left_quote_idx) = st.find('"') {
-        if let Some(right_quote_idx) = st.rfind('"') && left_quote_idx != right_quote_idx {
-            Ok(&st[left_quote_idx+1..right_quote_idx-1])
-        } else {
-            Err(SyntaxError::NoMatchingPair('"'))
-        }
-    } else {
-        Err(SyntaxError::NoMatchingPair('"'))
-    }
-}
 #[test]
 fn conversions() {
-    let n: u32 = parse_int_hex_or_dec("0x0A090807");
-    let v = to_size_N::<4>(n);
+    let n: u32 = parse_int_hex_or_dec("0x0A090807").unwrap() as u32;
+    let v = to_size_N::<4>(n).unwrap();
     dbg!(&v);
     assert!(v == [0x0A, 0x09, 0x08, 0x07]);
-    let n2: u32 = parse_int_hex_or_dec("0xffff");
-    let v2 = to_size_N::<2>(n2);
+    let n2: u32 = parse_int_hex_or_dec("0xffff").unwrap() as u32;
+    let v2 = to_size_N::<2>(n2).unwrap();
     dbg!(&v2);
     assert!(v2 == [0xff, 0xff]);
-    let n3: u32 = parse_int_hex_or_dec("2");
-    let v3 = to_size_N::<2>(n3);
+    let n3: u32 = parse_int_hex_or_dec("2").unwrap() as u32;
+    let v3 = to_size_N::<2>(n3).unwrap();
     dbg!(&v3);
     assert!(v3 == [0x00, 0x02])
 }
 
+/**
+ *  Parses a single `tag: .directive ...` line, for tests
+ */
+#[cfg(test)]
+fn try_parse_line(line: &str) -> Result<Data, Diagnostic> {
+    use super::Statement::StatementKind;
+    use crate::Functionality::Parser;
+
+    let (statements, errors) = Parser::parse(line, "test.s");
+    assert!(errors.is_empty());
+    match &statements[..] {
+        [label, directive] => match (&label.kind, &directive.kind) {
+            (StatementKind::Label(tag), StatementKind::Directive(name, args)) => Data::new(
+                name,
+                args,
+                tag.clone(),
+                &directive.span,
+                &SymbolTable::new(),
+                1 << 32,
+            ),
+            other => panic!("Not a data line: {:?}", other),
+        },
+        other => panic!("Not a data line: {:?}", other),
+    }
+}
+
+#[cfg(test)]
+pub fn parse_data_line(line: &str) -> Data {
+    try_parse_line(line).unwrap()
+}
+
 #[test]
 fn parsing() {
-    TagResolution::init();
-    let d: Data = "hello: .word 0xdeadbeef".into();
+    let d = parse_data_line("hello: .word 0xdeadbeef");
     dbg!(&d);
     assert!(d.contents == [0xde, 0xad, 0xbe, 0xef]);
 
-    let d2: Data = "some_text: .asciiz \"wow, cannot believe this string is zero terminated! unbelievable what you can do with strings\"".into();
+    let d2 = parse_data_line("some_text: .asciiz \"wow, cannot believe this string is zero terminated! unbelievable what you can do with strings\"");
     dbg!(&d2);
     assert!(&d2.contents[0..=2] == "wow".as_bytes());
     assert!(d2.contents[d2.contents.len() - 1] == "\0".as_bytes()[0]);
 
-    let d3: Data = "bss: .space 10".into();
+    let d3 = parse_data_line("bss: .space 10");
     dbg!(&d3);
     assert!(d3.contents.len() == 10);
 
-    let d4: Data = "num_vec: .half 1,  2,  3 ,4".into();
+    let d4 = parse_data_line("num_vec: .half 1,  2,  3 ,4");
     dbg!(&d4);
     assert!(d4.contents == [0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04]);
 }
+
+#[test]
+fn oversized_element_is_reported() {
+    let err = try_parse_line("bytes: .byte 1, 256, 3").unwrap_err();
+    assert!(err.span.unwrap().text() == "256");
+
+    let d = parse_data_line("signed: .half -1, -32768");
+    assert!(d.contents == [0xff, 0xff, 0x80, 0x00]);
+}
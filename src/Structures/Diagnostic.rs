@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+/**
+ *  A column range inside a single source line. Columns are 1-based and `col_end` is exclusive.
+ *  The text of the line is kept so the span can be rendered on its own. Lines produced by a
+ *  macro keep track of the invocation they came from in `expansion`
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+    pub line_text: String,
+    pub expansion: Option<Arc<MacroCall>>,
+}
+
+/**
+ *  A macro invocation: the name of the macro and where it was invoked
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct MacroCall {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Span {
+    /**
+     *  Span covering the contents of a whole line, leading and trailing whitespace excluded
+     */
+    pub fn of_line(file: &str, line: usize, text: &str) -> Self {
+        let start = text.len() - text.trim_start().len();
+        let end = text.trim_end().len().max(start + 1);
+        Span {
+            file: file.to_string(),
+            line,
+            col_start: start + 1,
+            col_end: end + 1,
+            line_text: text.to_string(),
+            expansion: None,
+        }
+    }
+
+    /**
+     *  The source text covered by the span
+     */
+    pub fn text(&self) -> &str {
+        let lo = (self.col_start - 1).min(self.line_text.len());
+        let hi = (self.col_end - 1).clamp(lo, self.line_text.len());
+        &self.line_text[lo..hi]
+    }
+
+    /**
+     *  Whether both spans are on the same line of the same source, and of the same expansion
+     */
+    pub fn same_line(&self, other: &Span) -> bool {
+        self.file == other.file && self.line == other.line && self.expansion == other.expansion
+    }
+
+    /**
+     *  Span from the start of `self` to the end of `other`, which must be on the same line
+     */
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            col_end: other.col_end.max(self.col_start),
+            ..self.clone()
+        }
+    }
+
+    /**
+     *  Narrows the span to the first occurrence of `fragment` inside it, ignoring case.
+     *  If the fragment cannot be found the span is returned unchanged
+     */
+    pub fn narrow(&self, fragment: &str) -> Span {
+        let fragment = fragment.trim();
+        let lo = (self.col_start - 1).min(self.line_text.len());
+        let hi = (self.col_end - 1).min(self.line_text.len());
+        match self.line_text[lo..hi]
+            .to_ascii_lowercase()
+            .find(&fragment.to_ascii_lowercase())
+        {
+            Some(idx) if !fragment.is_empty() => Span {
+                col_start: lo + idx + 1,
+                col_end: lo + idx + fragment.len() + 1,
+                ..self.clone()
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(span: &Span, message: impl std::fmt::Display) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            span: Some(span.clone()),
+            message: message.to_string(),
+        }
+    }
+
+    /**
+     *  A diagnostic that does not stop the assembly
+     */
+    pub fn warning(span: &Span, message: impl std::fmt::Display) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(span, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /**
+     *  A diagnostic that does not belong to any particular line
+     */
+    pub fn global(message: impl std::fmt::Display) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            span: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(f, "{}: {}", level, self.message)?;
+        if let Some(span) = &self.span {
+            render_span(f, span)?;
+            // follow the chain of macro invocations back to the source that was written
+            let mut call = span.expansion.as_ref();
+            while let Some(c) = call {
+                writeln!(f, "note: in expansion of macro \"{}\"", c.name)?;
+                render_span(f, &c.span)?;
+                call = c.span.expansion.as_ref();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn render_span(f: &mut std::fmt::Formatter<'_>, span: &Span) -> std::fmt::Result {
+    let gutter = " ".repeat(span.line.to_string().len());
+    // keep tabs so the caret lines up with the echoed source line
+    let pad: String = span
+        .line_text
+        .chars()
+        .take(span.col_start - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    writeln!(
+        f,
+        "{}--> {}:{}:{}",
+        gutter, span.file, span.line, span.col_start
+    )?;
+    writeln!(f, "{} |", gutter)?;
+    writeln!(f, "{} | {}", span.line, span.line_text)?;
+    writeln!(
+        f,
+        "{} | {}{}",
+        gutter,
+        pad,
+        "^".repeat(span.col_end.saturating_sub(span.col_start).max(1))
+    )
+}
+
+#[test]
+fn render_points_at_fragment() {
+    let span = Span::of_line("prog.s", 12, "    adid $t0, $t1, 4");
+    let diag = Diagnostic::new(&span.narrow("adid"), "Unrecognized opcode \"adid\"");
+    let rendered = diag.to_string();
+
+    assert!(rendered.contains("--> prog.s:12:5"));
+    assert!(rendered.contains("12 |     adid $t0, $t1, 4"));
+    assert!(rendered.ends_with("   |     ^^^^\n"));
+}
@@ -3,6 +3,8 @@
 pub enum MetadataError {
     NoSegmentData(String),
     Align(String),
+    Relocation(String),
+    SegmentOverflow,
 }
 
 impl std::fmt::Display for MetadataError {
@@ -17,6 +19,15 @@ impl std::fmt::Display for MetadataError {
                     "Base address for \"{segment_tag}\" segment is not word-aligned"
                 )
             }
+            MetadataError::Relocation(segment_tag) => {
+                write!(
+                    f,
+                    "Segment \"{segment_tag}\" cannot be moved once it has contents"
+                )
+            }
+            MetadataError::SegmentOverflow => {
+                write!(f, "Segment runs past the end of the address space")
+            }
         }
     }
 }
@@ -28,6 +39,14 @@ pub enum CompileError {
     RegisterParse(String),
     TagResolution(String),
     AlignmentError(u32, u32, String),
+    SegmentOverlap,
+    BranchRange(u32, u32),
+    JumpRegion(u32, u32),
+    JumpAlignment(u32),
+    ImmRange(i64, bool),
+    ExprOverflow,
+    DivisionByZero,
+    Redefinition(String),
 }
 
 impl std::fmt::Display for CompileError {
@@ -41,12 +60,32 @@ impl std::fmt::Display for CompileError {
             CompileError::AlignmentError(alignment, addr, symbol) => {
                 write!(
                     f,
-                    "type address is not {}-aligned: @ {} ; Symbol: {}",
-                    alignment,
-                    format!("{:08X}", addr),
-                    symbol
+                    "type address is not {}-aligned: @ {:08X} ; Symbol: {}",
+                    alignment, addr, symbol
                 )
             }
+            CompileError::SegmentOverlap => write!(f, "Code and Data segments overlap!"),
+            CompileError::BranchRange(target, addr) => write!(
+                f,
+                "Branch target {:08X} is out of range from {:08X}",
+                target, addr
+            ),
+            CompileError::JumpRegion(target, addr) => write!(
+                f,
+                "Jump target {:08X} is outside the 256MB region of the jump at {:08X}",
+                target, addr
+            ),
+            CompileError::JumpAlignment(target) => {
+                write!(f, "Jump target {:08X} is not word-aligned", target)
+            }
+            CompileError::ImmRange(v, unsigned) => write!(
+                f,
+                "Immediate {v} does not fit in a{} 16b field",
+                if *unsigned { "n unsigned" } else { " signed" }
+            ),
+            CompileError::ExprOverflow => write!(f, "Expression overflows 32b"),
+            CompileError::DivisionByZero => write!(f, "Division by zero in expression"),
+            CompileError::Redefinition(name) => write!(f, "\"{name}\" is already defined"),
         }
     }
 }
@@ -55,12 +94,206 @@ impl std::fmt::Display for CompileError {
 #[derive(Debug)]
 pub enum SyntaxError {
     NoMatchingPair(char),
+    UnrecognizedOpcode(String),
+    MissingOperand,
+    ExtraOperand(String),
+    InvalidInteger(String),
+    ValueTooLarge(i64, u32),
+    UnexpectedCharacter(char),
+    InvalidCharLiteral(String),
+    InvalidEscape(char),
+    UnknownRegister(String),
+    UnexpectedToken(String),
+    Expected(String),
+    UnknownDirective(String),
+    OutsideSegment(String, String),
+    IsaLevel(String, String, String),
+    AtReserved(String),
+    OddFpRegister(u32),
 }
 
 impl std::fmt::Display for SyntaxError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SyntaxError::NoMatchingPair(c) => write!(f, "Syntax error: No {} pair found", c),
+            SyntaxError::UnrecognizedOpcode(op) => write!(f, "Unrecognized opcode \"{op}\""),
+            SyntaxError::MissingOperand => write!(f, "Syntax error: Missing operand"),
+            SyntaxError::ExtraOperand(op) => {
+                write!(f, "Syntax error: Unexpected operand \"{op}\"")
+            }
+            SyntaxError::InvalidInteger(s) => {
+                write!(f, "\"{s}\" does not represent a valid integer")
+            }
+            SyntaxError::ValueTooLarge(v, bytes) => {
+                write!(f, "Value {v} does not fit in {bytes} byte(s)")
+            }
+            SyntaxError::UnexpectedCharacter(c) => {
+                write!(f, "Syntax error: Unexpected character '{c}'")
+            }
+            SyntaxError::InvalidCharLiteral(s) => write!(f, "Invalid char literal '{s}'"),
+            SyntaxError::InvalidEscape(c) => write!(f, "Unknown escape sequence \\{c}"),
+            SyntaxError::UnknownRegister(r) => write!(
+                f,
+                "Unknown register \"${r}\": expected $0-$31 or an ABI name such as $t0"
+            ),
+            SyntaxError::UnexpectedToken(t) => write!(f, "Syntax error: Unexpected {t}"),
+            SyntaxError::Expected(what) => write!(f, "Syntax error: Expected {what}"),
+            SyntaxError::UnknownDirective(d) => write!(f, "Unknown directive \"{d}\""),
+            SyntaxError::OutsideSegment(what, segment) => {
+                write!(f, "{what} is not allowed outside of the {segment} segment")
+            }
+            SyntaxError::IsaLevel(op, required, selected) => write!(
+                f,
+                "Instruction \"{op}\" requires {required}, but the target is {selected}"
+            ),
+            SyntaxError::AtReserved(op) => write!(
+                f,
+                "Pseudo-instruction \"{op}\" needs $at, which is reserved by .set noat"
+            ),
+            SyntaxError::OddFpRegister(n) => write!(
+                f,
+                "Double precision operands need an even register, $f{n} is odd"
+            ),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum AssemblyWarning {
+    TagCaseCollision(String, String),
+    AtClobbered,
+    LoadDelay(String, usize),
+    HiLoHazard(String, String, usize),
+}
+
+impl std::fmt::Display for AssemblyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblyWarning::TagCaseCollision(tag, other) => write!(
+                f,
+                "Tag \"{tag}\" differs only in case from tag \"{other}\"; tags are case-sensitive"
+            ),
+            AssemblyWarning::AtClobbered => write!(
+                f,
+                "$at may be overwritten by pseudo-instructions; use .set noat before using it"
+            ),
+            AssemblyWarning::LoadDelay(reg, line) => write!(
+                f,
+                "{reg} is used right after being loaded on line {line}, it still holds the old value"
+            ),
+            AssemblyWarning::HiLoHazard(op, read, line) => write!(
+                f,
+                "\"{op}\" follows \"{read}\" on line {line} too closely and may overwrite HI/LO before it is read"
+            ),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse(String, String),
+    UnknownFormat(String, String),
+    MissingField(String, String),
+    FieldWidth(String, String, u32, u32),
+    Invalid(String, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Parse(file, e) => write!(f, "Could not read extension file \"{file}\": {e}"),
+            ConfigError::UnknownFormat(name, format) => write!(
+                f,
+                "Instruction \"{name}\": unknown format \"{format}\", expected r, i, j, regimm, cop0, cop1, special2 or special"
+            ),
+            ConfigError::MissingField(name, field) => {
+                write!(f, "Instruction \"{name}\": missing field \"{field}\"")
+            }
+            ConfigError::FieldWidth(name, field, v, width) => write!(
+                f,
+                "Instruction \"{name}\": value {v} does not fit the {width}b field \"{field}\""
+            ),
+            ConfigError::Invalid(name, e) => write!(f, "Instruction \"{name}\": {e}"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum MacroError {
+    Unterminated(String),
+    StrayEnd,
+    Redefinition(String),
+    InvalidParameter(String),
+    MissingArgument(String, String),
+    UnknownParameter(String, String),
+    TooManyArguments(String),
+    TooDeep(String),
+}
+
+impl std::fmt::Display for MacroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacroError::Unterminated(name) => {
+                write!(f, "Macro \"{name}\" is missing its .endm")
+            }
+            MacroError::StrayEnd => write!(f, ".endm without a matching .macro"),
+            MacroError::Redefinition(name) => write!(f, "Macro \"{name}\" is already defined"),
+            MacroError::InvalidParameter(p) => write!(f, "Invalid macro parameter \"{p}\""),
+            MacroError::MissingArgument(param, name) => write!(
+                f,
+                "Missing argument for parameter \"{param}\" of macro \"{name}\""
+            ),
+            MacroError::UnknownParameter(param, name) => {
+                write!(f, "Macro \"{name}\" has no parameter \"{param}\"")
+            }
+            MacroError::TooManyArguments(name) => {
+                write!(f, "Too many arguments for macro \"{name}\"")
+            }
+            MacroError::TooDeep(name) => write!(
+                f,
+                "Macro \"{name}\" expands too deeply, it is probably recursive"
+            ),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum IncludeError {
+    NotFound(String),
+    Cycle(String),
+    Unreadable(String, String),
+}
+
+impl std::fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeError::NotFound(file) => {
+                write!(f, "Cannot find \"{file}\" in the include path")
+            }
+            IncludeError::Cycle(file) => write!(f, "\"{file}\" is included recursively"),
+            IncludeError::Unreadable(file, e) => write!(f, "Cannot read \"{file}\": {e}"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum ConditionalError {
+    Unmatched(String),
+    DuplicateElse,
+    Unterminated(String),
+}
+
+impl std::fmt::Display for ConditionalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalError::Unmatched(dir) => write!(f, "{dir} without a matching .if"),
+            ConditionalError::DuplicateElse => write!(f, "This .if already has an .else"),
+            ConditionalError::Unterminated(dir) => write!(f, "{dir} is missing its .endif"),
         }
     }
 }
@@ -0,0 +1,172 @@
+use super::Errors::CompileError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Xor,
+    Or,
+}
+
+/**
+ *  A constant expression. Symbols are looked up when it is evaluated, so an expression
+ *  may refer to tags defined after it
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(i64),
+    Symbol(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl BinaryOp {
+    /**
+     *  Binding strength, higher binds tighter. Same order as in C
+     */
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 7,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Shl | BinaryOp::Shr => 5,
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => 4,
+            BinaryOp::Eq | BinaryOp::Ne => 3,
+            BinaryOp::And => 2,
+            BinaryOp::Xor => 1,
+            BinaryOp::Or => 0,
+        }
+    }
+}
+
+impl Expr {
+    /**
+     *  Evaluates the expression, resolving symbols through `lookup`. Every intermediate
+     *  value has to fit in 32b, taken either as signed or unsigned. Comparisons are 1 when
+     *  they hold and 0 otherwise
+     */
+    pub fn eval(
+        &self,
+        lookup: &dyn Fn(&str) -> Result<i64, CompileError>,
+    ) -> Result<i64, CompileError> {
+        // wide enough that no operation on two 32b values can overflow before the check
+        let v: i128 = match self {
+            Expr::Num(v) => *v as i128,
+            Expr::Symbol(name) => lookup(name)? as i128,
+            Expr::Unary(UnaryOp::Neg, e) => -(e.eval(lookup)? as i128),
+            // complement in 32b, so ~0 is -1 and ~0xFFFFFFFF is 0
+            Expr::Unary(UnaryOp::Not, e) => !(e.eval(lookup)? as u32 as i32) as i128,
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(lookup)? as i128, rhs.eval(lookup)? as i128);
+                match op {
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div | BinaryOp::Rem if b == 0 => {
+                        return Err(CompileError::DivisionByZero)
+                    }
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Shl | BinaryOp::Shr if !(0..32).contains(&b) => {
+                        return Err(CompileError::ExprOverflow)
+                    }
+                    BinaryOp::Shl => a << b,
+                    BinaryOp::Shr => a >> b,
+                    BinaryOp::Lt => (a < b) as i128,
+                    BinaryOp::Gt => (a > b) as i128,
+                    BinaryOp::Le => (a <= b) as i128,
+                    BinaryOp::Ge => (a >= b) as i128,
+                    BinaryOp::Eq => (a == b) as i128,
+                    BinaryOp::Ne => (a != b) as i128,
+                    BinaryOp::And => a & b,
+                    BinaryOp::Xor => a ^ b,
+                    BinaryOp::Or => a | b,
+                }
+            }
+        };
+        if !(i32::MIN as i128..=u32::MAX as i128).contains(&v) {
+            return Err(CompileError::ExprOverflow);
+        }
+        Ok(v as i64)
+    }
+
+    /**
+     *  Whether the expression refers to any symbol
+     */
+    pub fn has_symbols(&self) -> bool {
+        match self {
+            Expr::Num(_) => false,
+            Expr::Symbol(_) => true,
+            Expr::Unary(_, e) => e.has_symbols(),
+            Expr::Binary(_, lhs, rhs) => lhs.has_symbols() || rhs.has_symbols(),
+        }
+    }
+}
+
+#[test]
+fn evaluation() {
+    use BinaryOp::*;
+
+    let num = |v| Box::new(Expr::Num(v));
+    let sym = |s: &str| Box::new(Expr::Symbol(s.into()));
+    let lookup = |name: &str| match name {
+        "start" => Ok(0x400),
+        "end" => Ok(0x420),
+        _ => Err(CompileError::TagResolution(name.into())),
+    };
+
+    let len = Expr::Binary(
+        Shr,
+        Box::new(Expr::Binary(Sub, sym("end"), sym("start"))),
+        num(2),
+    );
+    assert!(len.eval(&lookup).unwrap() == 8);
+    assert!(Expr::Unary(UnaryOp::Not, num(0)).eval(&lookup).unwrap() == -1);
+    assert!(Expr::Binary(Rem, num(-7), num(3)).eval(&lookup).unwrap() == -1);
+    assert!(
+        Expr::Binary(Ge, sym("end"), sym("start"))
+            .eval(&lookup)
+            .unwrap()
+            == 1
+    );
+    assert!(
+        Expr::Binary(Eq, num(-1), num(0xFFFFFFFF))
+            .eval(&lookup)
+            .unwrap()
+            == 0
+    );
+
+    assert!(matches!(
+        Expr::Binary(Add, num(0xFFFFFFFF), num(1)).eval(&lookup),
+        Err(CompileError::ExprOverflow)
+    ));
+    assert!(matches!(
+        Expr::Binary(Mul, num(0xFFFFFFFF), num(0xFFFFFFFF)).eval(&lookup),
+        Err(CompileError::ExprOverflow)
+    ));
+    assert!(matches!(
+        Expr::Binary(Div, num(1), num(0)).eval(&lookup),
+        Err(CompileError::DivisionByZero)
+    ));
+    assert!(matches!(
+        Expr::Binary(Add, sym("nowhere"), num(1)).eval(&lookup),
+        Err(CompileError::TagResolution(_))
+    ));
+}
@@ -1,121 +1,169 @@
 use std::collections::VecDeque;
+use std::sync::Arc;
 
 use super::ArgumentBundle::ArgumentBundle;
-use super::Opcodes::OPCODES;
-
-#[derive(PartialEq, Eq, Debug)]
-pub enum InstructionType {
-    I,
-    R,
-    J,
-    Special,
+use super::Diagnostic::{Diagnostic, Span};
+use super::Errors::SyntaxError;
+use super::InstructionTable::{ImmKind, InstrDef, InstructionTable};
+use super::Statement::Operand;
+use crate::Functionality::TagResolution::SymbolTable;
+
+/**
+ *  Instruction set level targeted by an assembly. Each level includes the ones below it
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum IsaLevel {
+    #[default]
+    Mips1,
+    Mips2,
+    Mips32,
+}
+
+impl std::fmt::Display for IsaLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsaLevel::Mips1 => write!(f, "MIPS I"),
+            IsaLevel::Mips2 => write!(f, "MIPS II"),
+            IsaLevel::Mips32 => write!(f, "MIPS32"),
+        }
+    }
+}
+
+impl std::str::FromStr for IsaLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mips1" | "1" => Ok(IsaLevel::Mips1),
+            "mips2" | "2" => Ok(IsaLevel::Mips2),
+            "mips32" | "32" => Ok(IsaLevel::Mips32),
+            _ => Err(format!(
+                "Unknown ISA level \"{s}\", expected mips1, mips2 or mips32"
+            )),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Instruction {
-    pub itype: InstructionType,
-    pub func: u32,
+    pub def: Arc<InstrDef>,
     pub args: ArgumentBundle,
+    pub note: Option<String>, // shown next to the instruction in the listing
 }
 
-impl From<String> for Instruction {
-    fn from(st: String) -> Self {
-        //Parse the string
-        let mut tokens: VecDeque<&str> = st.split(|c: char| c == ' ' || c == ',').collect();
-        //1. Figure out OP type
-        let op = tokens.pop_front().expect("Malformed instruction");
-        let (func, itype) = match_func(op);
-
-        //1.1: If itype == special, func describes the entire instruction and we return early
-        if itype == InstructionType::Special {
-            return Instruction {
-                itype,
-                func,
-                args: ArgumentBundle::default(),
-            };
+impl Instruction {
+    /**
+     *  Stands in for a line that failed to parse, so the addresses after it stay consistent
+     */
+    pub fn placeholder() -> Self {
+        Instruction {
+            def: Arc::new(InstrDef {
+                mnemonic: "nop".into(),
+                word: 0,
+                operands: Vec::new(),
+                imm: ImmKind::None,
+                isa: IsaLevel::Mips1,
+            }),
+            args: ArgumentBundle::default(),
+            note: None,
         }
-        //2. Construct appropiate ArgumentBundle
+    }
 
-        let args = ArgumentBundle::construct(tokens, &itype, func);
+    /**
+     *  Builds an instruction from its parsed mnemonic and operands, logging any tag it
+     *  references in `symbols`. Mnemonics above the `isa` level are rejected
+     */
+    pub fn new(
+        mnemonic: &str,
+        operands: Vec<Operand>,
+        span: &Span,
+        symbols: &mut SymbolTable,
+        table: &InstructionTable,
+        isa: IsaLevel,
+    ) -> Result<Self, Diagnostic> {
+        let def = table.lookup(mnemonic, operands.len()).ok_or_else(|| {
+            Diagnostic::new(
+                &span.narrow(mnemonic),
+                SyntaxError::UnrecognizedOpcode(mnemonic.into()),
+            )
+        })?;
+        if def.isa > isa {
+            return Err(Diagnostic::new(
+                &span.narrow(mnemonic),
+                SyntaxError::IsaLevel(mnemonic.into(), def.isa.to_string(), isa.to_string()),
+            ));
+        }
 
-        //??
-        //Profit!
+        let tokens: VecDeque<Operand> = operands.into();
+        let args = ArgumentBundle::construct(tokens, &def, span, symbols)?;
 
-        Instruction { itype, func, args }
+        Ok(Instruction {
+            def,
+            args,
+            note: None,
+        })
     }
 }
 
-impl From<&str> for Instruction {
-    fn from(st: &str) -> Self {
-        st.to_string().into()
-    }
+/**
+ *  Parses a single line holding one instruction, for tests. Every ISA level is allowed
+ */
+#[cfg(test)]
+pub fn parse_instruction(line: &str) -> Result<Instruction, Diagnostic> {
+    parse_instruction_for(line, IsaLevel::Mips32)
 }
 
-fn match_func(op: &str) -> (u32, InstructionType) {
-    match op {
-        "add" => (OPCODES::R::ADD, InstructionType::R),
-        "addu" => (OPCODES::R::ADDU, InstructionType::R),
-        "and" => (OPCODES::R::AND, InstructionType::R),
-        "nor" => (OPCODES::R::NOR, InstructionType::R),
-        "or" => (OPCODES::R::OR, InstructionType::R),
-        "sub" => (OPCODES::R::SUB, InstructionType::R),
-        "subu" => (OPCODES::R::SUBU, InstructionType::R),
-        "xor" => (OPCODES::R::XOR, InstructionType::R),
-        "slt" => (OPCODES::R::SLT, InstructionType::R),
-        "sltu" => (OPCODES::R::SLTU, InstructionType::R),
-        "div" => (OPCODES::R::DIV, InstructionType::R),
-        "divu" => (OPCODES::R::DIVU, InstructionType::R),
-        "mult" => (OPCODES::R::MULT, InstructionType::R),
-        "multu" => (OPCODES::R::MULTU, InstructionType::R),
-        "sll" => (OPCODES::R::SLL, InstructionType::R),
-        "sra" => (OPCODES::R::SRA, InstructionType::R),
-        "srav" => (OPCODES::R::SRAV, InstructionType::R),
-        "srlv" => (OPCODES::R::SRLV, InstructionType::R),
-        "jarl" => (OPCODES::R::JARL, InstructionType::R),
-        "jr" => (OPCODES::R::JR, InstructionType::R),
-        "mfhi" => (OPCODES::R::MFHI, InstructionType::R),
-        "mflo" => (OPCODES::R::MFLO, InstructionType::R),
-        "mthi" => (OPCODES::R::MTHI, InstructionType::R),
-        "mtlo" => (OPCODES::R::MTLO, InstructionType::R),
-
-        "addi" => (OPCODES::I::ADDI, InstructionType::I),
-        "addiu" => (OPCODES::I::ADDIU, InstructionType::I),
-        "andi" => (OPCODES::I::ANDI, InstructionType::I),
-        "ori" => (OPCODES::I::ORI, InstructionType::I),
-        "xori" => (OPCODES::I::XORI, InstructionType::I),
-        "slti" => (OPCODES::I::SLTI, InstructionType::I),
-        "sltiu" => (OPCODES::I::SLTIU, InstructionType::I),
-        "lhi" => (OPCODES::I::LHI, InstructionType::I),
-        "llo" => (OPCODES::I::LLO, InstructionType::I),
-        "beq" => (OPCODES::I::BEQ, InstructionType::I),
-        "bne" => (OPCODES::I::BNE, InstructionType::I),
-        "bgtz" => (OPCODES::I::BGTZ, InstructionType::I),
-        "blez" => (OPCODES::I::BLEZ, InstructionType::I),
-        "lb" => (OPCODES::I::LB, InstructionType::I),
-        "lbu" => (OPCODES::I::LBU, InstructionType::I),
-        "lh" => (OPCODES::I::LH, InstructionType::I),
-        "lhu" => (OPCODES::I::LHU, InstructionType::I),
-        "lw" => (OPCODES::I::LW, InstructionType::I),
-        "sb" => (OPCODES::I::SB, InstructionType::I),
-        "sh" => (OPCODES::I::SH, InstructionType::I),
-        "sw" => (OPCODES::I::SW, InstructionType::I),
-
-        "jal" => (OPCODES::J::JAL, InstructionType::J),
-        "j" => (OPCODES::J::J, InstructionType::J),
-
-        "hlt" => (OPCODES::HLT, InstructionType::Special),
-        "rfe" => (OPCODES::RFE, InstructionType::Special),
-        "nop" => (OPCODES::NOP, InstructionType::Special),
-        "syscall" => (OPCODES::SYSCALL, InstructionType::Special),
-
-        _ => {
-            panic!("Unrecognized opcode {}", op);
-        }
+#[cfg(test)]
+pub fn parse_instruction_for(line: &str, isa: IsaLevel) -> Result<Instruction, Diagnostic> {
+    use super::Statement::StatementKind;
+    use crate::Functionality::Parser;
+
+    let (statements, mut errors) = Parser::parse(line, "test.s");
+    if !errors.is_empty() {
+        return Err(errors.remove(0));
+    }
+    match statements.into_iter().last() {
+        Some(super::Statement::Statement {
+            kind: StatementKind::Instruction(mnemonic, operands),
+            span,
+        }) => Instruction::new(
+            &mnemonic,
+            operands,
+            &span,
+            &mut SymbolTable::new(),
+            &InstructionTable::new(),
+            isa,
+        ),
+        other => panic!("Not an instruction: {:?}", other),
     }
 }
 
 #[test]
 fn conversion() {
-    let instr: Instruction = "addi 1,2,3".into();
-    dbg!(instr);
+    let instr = parse_instruction("addi $at, $v0, 3");
+    dbg!(instr.unwrap());
+}
+
+#[test]
+fn unrecognized_opcode_is_reported() {
+    let err = parse_instruction("\n\n\n  adid $at, $v0, 3").unwrap_err();
+    let span = err.span.unwrap();
+    assert!(span.line == 4 && span.col_start == 3 && span.col_end == 7);
+
+    let err = parse_instruction("addi $at, $v0").unwrap_err();
+    assert!(err.message.contains("Missing operand"));
+}
+
+#[test]
+fn isa_levels_gate_mnemonics() {
+    assert!(parse_instruction_for("sync", IsaLevel::Mips2).is_ok());
+    assert!(parse_instruction_for("mul $t0, $t1, $t2", IsaLevel::Mips32).is_ok());
+
+    let err = parse_instruction_for("  mul $t0, $t1, $t2", IsaLevel::Mips2).unwrap_err();
+    assert!(err.message.contains("MIPS32") && err.message.contains("MIPS II"));
+    assert!(err.span.unwrap().col_start == 3);
+    assert!(parse_instruction_for("ll $t0, 0($a0)", IsaLevel::Mips1).is_err());
+
+    assert!("MIPS32".parse::<IsaLevel>() == Ok(IsaLevel::Mips32));
+    assert!("mips4".parse::<IsaLevel>().is_err());
 }
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::Instruction::IsaLevel;
+use super::Register::{CopRegister, Register};
+
+/**
+ *  One operand slot of an instruction, as written in an operand pattern
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Rs,
+    Rt,
+    Rd,
+    RdRt, // written once, encoded in both rd and rt
+    Sa,
+    Imm,
+    Label,  // branch target, encoded as a word offset
+    Target, // jump target, encoded as a 26b pseudo-absolute address
+    Mem,    // offset(base), base goes in rs
+    Fs,
+    Ft,
+    Fd,
+    Cs, // coprocessor 0 register, encoded in the rd field
+}
+
+impl Field {
+    fn parse(s: &str) -> Option<Field> {
+        Some(match s {
+            "rs" => Field::Rs,
+            "rt" => Field::Rt,
+            "rd" => Field::Rd,
+            "rd=rt" => Field::RdRt,
+            "sa" => Field::Sa,
+            "imm" => Field::Imm,
+            "label" => Field::Label,
+            "target" => Field::Target,
+            "offset(base)" => Field::Mem,
+            "fs" => Field::Fs,
+            "ft" => Field::Ft,
+            "fd" => Field::Fd,
+            "cs" => Field::Cs,
+            _ => return None,
+        })
+    }
+
+    /**
+     *  Bits of the instruction word taken up by the field
+     */
+    fn bits(self) -> u32 {
+        match self {
+            Field::Rs => 0x03E00000,
+            Field::Rt | Field::Ft => 0x001F0000,
+            Field::Rd | Field::Fs | Field::Cs => 0x0000F800,
+            Field::RdRt => 0x001FF800,
+            Field::Sa | Field::Fd => 0x000007C0,
+            Field::Imm | Field::Label => 0x0000FFFF,
+            Field::Target => 0x03FFFFFF,
+            Field::Mem => 0x03E0FFFF,
+        }
+    }
+}
+
+/**
+ *  How the 16b (or 26b, for jumps) immediate of an instruction is interpreted
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImmKind {
+    None,
+    Signed,
+    Unsigned,
+    Branch,
+    Jump,
+}
+
+/**
+ *  Definition of a mnemonic: the bits that are always set in its encoding and the operands
+ *  that fill in the rest
+ */
+#[derive(Debug, Clone)]
+pub struct InstrDef {
+    pub mnemonic: String,
+    pub word: u32,
+    pub operands: Vec<Field>,
+    pub imm: ImmKind,
+    pub isa: IsaLevel,
+}
+
+impl InstrDef {
+    /**
+     *  Builds a definition from an operand pattern such as `rd, rs, rt` or `rt, offset(base)`
+     */
+    pub fn new(
+        mnemonic: &str,
+        word: u32,
+        pattern: &str,
+        imm: ImmKind,
+        isa: IsaLevel,
+    ) -> Result<Self, String> {
+        let operands = pattern
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| Field::parse(f).ok_or_else(|| format!("Unknown operand \"{f}\"")))
+            .collect::<Result<Vec<Field>, String>>()?;
+
+        Ok(InstrDef {
+            mnemonic: mnemonic.to_ascii_lowercase(),
+            word,
+            operands,
+            imm,
+            isa,
+        })
+    }
+
+    /**
+     *  Bits that are fixed by the definition and not by its operands
+     */
+    pub fn mask(&self) -> u32 {
+        !self.operands.iter().fold(0, |acc, f| acc | f.bits())
+    }
+}
+
+const fn r(funct: u32) -> u32 {
+    funct
+}
+const fn i(opcode: u32) -> u32 {
+    opcode << 26
+}
+const fn regimm(sub: u32) -> u32 {
+    (0x01 << 26) | (sub << 16)
+}
+const fn cop(z: u32, sub: u32) -> u32 {
+    ((0x10 + z) << 26) | (sub << 21)
+}
+const fn fpu(fmt: u32, funct: u32) -> u32 {
+    cop(1, fmt) | funct
+}
+const fn special2(funct: u32) -> u32 {
+    (0x1C << 26) | funct
+}
+
+const FMT_S: u32 = 0x10;
+const FMT_D: u32 = 0x11;
+const FMT_W: u32 = 0x14;
+
+use ImmKind::{Branch, Jump, Signed, Unsigned};
+use IsaLevel::{Mips1, Mips2, Mips32};
+
+/**
+ *  Every built in mnemonic. When two definitions encode the same word the first one is used
+ *  for disassembly, so `nop` goes before `sll` and `rfe` before `hlt`. `hlt` is this
+ *  assembler's old name for the 0x42000010 word, which the R3000 defines as `rfe`; it is
+ *  still accepted so existing programs keep assembling, but it always disassembles as `rfe`
+ */
+#[rustfmt::skip]
+const BUILTIN: &[(&str, u32, &str, ImmKind, IsaLevel)] = &[
+    ("nop",     r(0x00),              "",                ImmKind::None, Mips1),
+    ("sll",     r(0x00),              "rd, rt, sa",      ImmKind::None, Mips1),
+    ("srl",     r(0x02),              "rd, rt, sa",      ImmKind::None, Mips1),
+    ("sra",     r(0x03),              "rd, rt, sa",      ImmKind::None, Mips1),
+    ("sllv",    r(0x04),              "rd, rt, rs",      ImmKind::None, Mips1),
+    ("srlv",    r(0x06),              "rd, rt, rs",      ImmKind::None, Mips1),
+    ("srav",    r(0x07),              "rd, rt, rs",      ImmKind::None, Mips1),
+    ("jr",      r(0x08),              "rs",              ImmKind::None, Mips1),
+    ("jalr",    r(0x09) | (31 << 11), "rs",              ImmKind::None, Mips1),
+    ("jalr",    r(0x09),              "rd, rs",          ImmKind::None, Mips1),
+    ("movz",    r(0x0A),              "rd, rs, rt",      ImmKind::None, Mips32),
+    ("movn",    r(0x0B),              "rd, rs, rt",      ImmKind::None, Mips32),
+    ("syscall", r(0x0C),              "",                ImmKind::None, Mips1),
+    ("break",   r(0x0D),              "",                ImmKind::None, Mips1),
+    ("sync",    r(0x0F),              "",                ImmKind::None, Mips2),
+    ("mfhi",    r(0x10),              "rd",              ImmKind::None, Mips1),
+    ("mthi",    r(0x11),              "rs",              ImmKind::None, Mips1),
+    ("mflo",    r(0x12),              "rd",              ImmKind::None, Mips1),
+    ("mtlo",    r(0x13),              "rs",              ImmKind::None, Mips1),
+    ("mult",    r(0x18),              "rs, rt",          ImmKind::None, Mips1),
+    ("multu",   r(0x19),              "rs, rt",          ImmKind::None, Mips1),
+    ("div",     r(0x1A),              "rs, rt",          ImmKind::None, Mips1),
+    ("divu",    r(0x1B),              "rs, rt",          ImmKind::None, Mips1),
+    ("add",     r(0x20),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("addu",    r(0x21),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("sub",     r(0x22),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("subu",    r(0x23),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("and",     r(0x24),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("or",      r(0x25),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("xor",     r(0x26),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("nor",     r(0x27),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("slt",     r(0x2A),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("sltu",    r(0x2B),              "rd, rs, rt",      ImmKind::None, Mips1),
+    ("teq",     r(0x34),              "rs, rt",          ImmKind::None, Mips2),
+    ("tne",     r(0x36),              "rs, rt",          ImmKind::None, Mips2),
+
+    ("bltz",    regimm(0x00),         "rs, label",       Branch,        Mips1),
+    ("bgez",    regimm(0x01),         "rs, label",       Branch,        Mips1),
+    ("bltzal",  regimm(0x10),         "rs, label",       Branch,        Mips1),
+    ("bgezal",  regimm(0x11),         "rs, label",       Branch,        Mips1),
+
+    ("j",       i(0x02),              "target",          Jump,          Mips1),
+    ("jal",     i(0x03),              "target",          Jump,          Mips1),
+
+    ("beq",     i(0x04),              "rs, rt, label",   Branch,        Mips1),
+    ("bne",     i(0x05),              "rs, rt, label",   Branch,        Mips1),
+    ("blez",    i(0x06),              "rs, label",       Branch,        Mips1),
+    ("bgtz",    i(0x07),              "rs, label",       Branch,        Mips1),
+    ("addi",    i(0x08),              "rt, rs, imm",     Signed,        Mips1),
+    ("addiu",   i(0x09),              "rt, rs, imm",     Signed,        Mips1),
+    ("slti",    i(0x0A),              "rt, rs, imm",     Signed,        Mips1),
+    ("sltiu",   i(0x0B),              "rt, rs, imm",     Signed,        Mips1),
+    ("andi",    i(0x0C),              "rt, rs, imm",     Unsigned,      Mips1),
+    ("ori",     i(0x0D),              "rt, rs, imm",     Unsigned,      Mips1),
+    ("xori",    i(0x0E),              "rt, rs, imm",     Unsigned,      Mips1),
+    ("lui",     i(0x0F),              "rt, imm",         Unsigned,      Mips1),
+    ("beql",    i(0x14),              "rs, rt, label",   Branch,        Mips2),
+    ("bnel",    i(0x15),              "rs, rt, label",   Branch,        Mips2),
+    ("llo",     i(0x18),              "rt, imm",         Unsigned,      Mips1),
+    ("lhi",     i(0x19),              "rt, imm",         Unsigned,      Mips1),
+    ("lb",      i(0x20),              "rt, offset(base)", Signed,       Mips1),
+    ("lh",      i(0x21),              "rt, offset(base)", Signed,       Mips1),
+    ("lwl",     i(0x22),              "rt, offset(base)", Signed,       Mips1),
+    ("lw",      i(0x23),              "rt, offset(base)", Signed,       Mips1),
+    ("lbu",     i(0x24),              "rt, offset(base)", Signed,       Mips1),
+    ("lhu",     i(0x25),              "rt, offset(base)", Signed,       Mips1),
+    ("lwr",     i(0x26),              "rt, offset(base)", Signed,       Mips1),
+    ("sb",      i(0x28),              "rt, offset(base)", Signed,       Mips1),
+    ("sh",      i(0x29),              "rt, offset(base)", Signed,       Mips1),
+    ("swl",     i(0x2A),              "rt, offset(base)", Signed,       Mips1),
+    ("sw",      i(0x2B),              "rt, offset(base)", Signed,       Mips1),
+    ("swr",     i(0x2E),              "rt, offset(base)", Signed,       Mips1),
+    ("ll",      i(0x30),              "rt, offset(base)", Signed,       Mips2),
+    ("lwc1",    i(0x31),              "ft, offset(base)", Signed,       Mips1),
+    ("sc",      i(0x38),              "rt, offset(base)", Signed,       Mips2),
+    ("swc1",    i(0x39),              "ft, offset(base)", Signed,       Mips1),
+
+    ("mfc0",    cop(0, 0x00),         "rt, cs",          ImmKind::None, Mips1),
+    ("mtc0",    cop(0, 0x04),         "rt, cs",          ImmKind::None, Mips1),
+    ("tlbr",    cop(0, 0x10) | 0x01,  "",                ImmKind::None, Mips1),
+    ("tlbwi",   cop(0, 0x10) | 0x02,  "",                ImmKind::None, Mips1),
+    ("tlbwr",   cop(0, 0x10) | 0x06,  "",                ImmKind::None, Mips1),
+    ("tlbp",    cop(0, 0x10) | 0x08,  "",                ImmKind::None, Mips1),
+    ("rfe",     cop(0, 0x10) | 0x10,  "",                ImmKind::None, Mips1),
+    ("hlt",     cop(0, 0x10) | 0x10,  "",                ImmKind::None, Mips1), // alias of rfe
+
+    ("mfc1",    cop(1, 0x00),         "rt, fs",          ImmKind::None, Mips1),
+    ("mtc1",    cop(1, 0x04),         "rt, fs",          ImmKind::None, Mips1),
+    ("bc1f",    cop(1, 0x08),         "label",           Branch,        Mips1),
+    ("bc1t",    cop(1, 0x08) | (1 << 16), "label",       Branch,        Mips1),
+    ("cvt.s.d", fpu(FMT_D, 0x20),     "fd, fs",          ImmKind::None, Mips1),
+    ("cvt.s.w", fpu(FMT_W, 0x20),     "fd, fs",          ImmKind::None, Mips1),
+    ("cvt.d.s", fpu(FMT_S, 0x21),     "fd, fs",          ImmKind::None, Mips1),
+    ("cvt.d.w", fpu(FMT_W, 0x21),     "fd, fs",          ImmKind::None, Mips1),
+    ("cvt.w.s", fpu(FMT_S, 0x24),     "fd, fs",          ImmKind::None, Mips1),
+    ("cvt.w.d", fpu(FMT_D, 0x24),     "fd, fs",          ImmKind::None, Mips1),
+
+    ("madd",    special2(0x00),       "rs, rt",          ImmKind::None, Mips32),
+    ("maddu",   special2(0x01),       "rs, rt",          ImmKind::None, Mips32),
+    ("mul",     special2(0x02),       "rd, rs, rt",      ImmKind::None, Mips32),
+    ("msub",    special2(0x04),       "rs, rt",          ImmKind::None, Mips32),
+    ("msubu",   special2(0x05),       "rs, rt",          ImmKind::None, Mips32),
+    ("clz",     special2(0x20),       "rd=rt, rs",       ImmKind::None, Mips32),
+    ("clo",     special2(0x21),       "rd=rt, rs",       ImmKind::None, Mips32),
+];
+
+/**
+ *  Floating point operations, written `<op>.<fmt>`
+ */
+const FPU_OPS: &[(&str, u32, &str)] = &[
+    ("add", 0x00, "fd, fs, ft"),
+    ("sub", 0x01, "fd, fs, ft"),
+    ("mul", 0x02, "fd, fs, ft"),
+    ("div", 0x03, "fd, fs, ft"),
+    ("abs", 0x05, "fd, fs"),
+    ("mov", 0x06, "fd, fs"),
+    ("neg", 0x07, "fd, fs"),
+];
+
+/**
+ *  Comparisons, written `c.<cond>.<fmt>`. The function code is 0x30 plus the index here
+ */
+const FPU_CONDITIONS: [&str; 16] = [
+    "f", "un", "eq", "ueq", "olt", "ult", "ole", "ule", "sf", "ngle", "seq", "ngl", "lt", "nge",
+    "le", "ngt",
+];
+
+/**
+ *  The set of mnemonics an assembly understands. Drives parsing, encoding and disassembly
+ */
+#[derive(Debug, Clone)]
+pub struct InstructionTable {
+    defs: Vec<Arc<InstrDef>>,
+    by_mnemonic: HashMap<String, Vec<usize>>,
+}
+
+impl Default for InstructionTable {
+    fn default() -> Self {
+        let mut table = InstructionTable {
+            defs: Vec::new(),
+            by_mnemonic: HashMap::new(),
+        };
+
+        for (mnemonic, word, pattern, imm, isa) in BUILTIN {
+            table.add(InstrDef::new(mnemonic, *word, pattern, *imm, *isa).unwrap());
+        }
+
+        let formats = [("s", FMT_S), ("d", FMT_D)];
+        for (op, funct, pattern) in FPU_OPS {
+            for (suffix, fmt) in formats {
+                let mnemonic = format!("{op}.{suffix}");
+                let def = InstrDef::new(&mnemonic, fpu(fmt, *funct), pattern, ImmKind::None, Mips1);
+                table.add(def.unwrap());
+            }
+        }
+        for (cond_idx, cond) in FPU_CONDITIONS.iter().enumerate() {
+            for (suffix, fmt) in formats {
+                let mnemonic = format!("c.{cond}.{suffix}");
+                let word = fpu(fmt, 0x30 | cond_idx as u32);
+                let def = InstrDef::new(&mnemonic, word, "fs, ft", ImmKind::None, Mips1);
+                table.add(def.unwrap());
+            }
+        }
+
+        table
+    }
+}
+
+impl InstructionTable {
+    pub fn new() -> Self {
+        InstructionTable::default()
+    }
+
+    /**
+     *  Adds a definition. A mnemonic may have several definitions with different operand
+     *  counts, like `jalr rs` and `jalr rd, rs`
+     */
+    pub fn add(&mut self, def: InstrDef) {
+        self.by_mnemonic
+            .entry(def.mnemonic.clone())
+            .or_default()
+            .push(self.defs.len());
+        self.defs.push(Arc::new(def));
+    }
+
+    /**
+     *  Adds user supplied definitions. A mnemonic defined here replaces every built in
+     *  definition of the same name, and the new definitions take precedence in disassembly
+     */
+    pub fn extend(&mut self, defs: Vec<InstrDef>) {
+        let overridden: Vec<String> = defs.iter().map(|d| d.mnemonic.clone()).collect();
+        let old = std::mem::take(&mut self.defs);
+        self.by_mnemonic.clear();
+
+        for def in defs {
+            self.add(def);
+        }
+        for def in old {
+            if !overridden.contains(&def.mnemonic) {
+                self.by_mnemonic
+                    .entry(def.mnemonic.clone())
+                    .or_default()
+                    .push(self.defs.len());
+                self.defs.push(def);
+            }
+        }
+    }
+
+    pub fn contains(&self, mnemonic: &str) -> bool {
+        self.by_mnemonic.contains_key(mnemonic)
+    }
+
+    /**
+     *  Finds the definition of `mnemonic` taking `operand_count` operands. If none takes that
+     *  many, the first one is returned so the operand mismatch can be reported against it
+     */
+    pub fn lookup(&self, mnemonic: &str, operand_count: usize) -> Option<Arc<InstrDef>> {
+        let candidates = self.by_mnemonic.get(mnemonic)?;
+        let idx = candidates
+            .iter()
+            .find(|idx| self.defs[**idx].operands.len() == operand_count)
+            .unwrap_or(&candidates[0]);
+        Some(self.defs[*idx].clone())
+    }
+
+    /**
+     *  Renders an encoded word back into assembly. Branch and jump targets are shown as
+     *  absolute addresses, computed from `addr`
+     */
+    pub fn disassemble(&self, word: u32, addr: u32) -> Option<String> {
+        let def = self.defs.iter().find(|d| word & d.mask() == d.word)?;
+
+        let reg = |shift: u32| Register::from_bits(word >> shift);
+        let cop = |shift: u32| CopRegister::from_bits(word >> shift).number();
+        let imm16 = word & 0xFFFF;
+
+        let operands: Vec<String> = def
+            .operands
+            .iter()
+            .map(|f| match f {
+                Field::Rs => reg(21).to_string(),
+                Field::Rt => reg(16).to_string(),
+                Field::Rd | Field::RdRt => reg(11).to_string(),
+                Field::Sa => ((word >> 6) & 0x1F).to_string(),
+                Field::Imm if def.imm == ImmKind::Signed => (imm16 as i16).to_string(),
+                Field::Imm => format!("0x{:X}", imm16),
+                Field::Label => {
+                    let offset = (imm16 as i16 as i32) << 2;
+                    format!("0x{:08X}", addr.wrapping_add(4).wrapping_add(offset as u32))
+                }
+                Field::Target => format!(
+                    "0x{:08X}",
+                    (addr.wrapping_add(4) & 0xF0000000) | ((word & 0x03FFFFFF) << 2)
+                ),
+                Field::Mem => format!("{}({})", imm16 as i16, reg(21)),
+                Field::Fs => format!("$f{}", cop(11)),
+                Field::Ft => format!("$f{}", cop(16)),
+                Field::Fd => format!("$f{}", cop(6)),
+                Field::Cs => format!("${}", cop(11)),
+            })
+            .collect();
+
+        Some(if operands.is_empty() {
+            def.mnemonic.clone()
+        } else {
+            format!("{} {}", def.mnemonic, operands.join(", "))
+        })
+    }
+}
+
+#[test]
+fn disassembly() {
+    let table = InstructionTable::new();
+    let cases = [
+        (0x00000000, 0x0, "nop"),
+        (0x00094100, 0x0, "sll $t0, $t1, 4"),
+        (0x012A4020, 0x0, "add $t0, $t1, $t2"),
+        (0x2108FFFF, 0x0, "addi $t0, $t0, -1"),
+        (0x3C08FFFF, 0x0, "lui $t0, 0xFFFF"),
+        (0x8FA9FFFC, 0x0, "lw $t1, -4($sp)"),
+        (0x1500FFFE, 0x8, "bne $t0, $zero, 0x00000004"),
+        (0x08000004, 0x0, "j 0x00000010"),
+        (0x0320F809, 0x0, "jalr $t9"),
+        (0x03204009, 0x0, "jalr $t0, $t9"),
+        (0x71284020, 0x0, "clz $t0, $t1"),
+        (0x401A6800, 0x0, "mfc0 $k0, $13"),
+        (0x42000010, 0x0, "rfe"),
+        (0x46262082, 0x0, "mul.d $f2, $f4, $f6"),
+        (0x4624103C, 0x0, "c.lt.d $f2, $f4"),
+        (0xC7A00004, 0x0, "lwc1 $f0, 4($sp)"),
+    ];
+
+    for (word, addr, text) in cases {
+        assert!(
+            table.disassemble(word, addr).as_deref() == Some(text),
+            "{text}"
+        );
+    }
+    assert!(table.disassemble(0xFC000000, 0x0).is_none());
+    assert!(table.lookup("hlt", 0).unwrap().word == table.lookup("rfe", 0).unwrap().word);
+}
+
+#[test]
+fn extensions_override_builtins() {
+    let mut table = InstructionTable::new();
+    table.extend(vec![
+        InstrDef::new("hlt", 0xFC000000, "", ImmKind::None, Mips1).unwrap(),
+        InstrDef::new("lw", 0x8C000000, "rt, rs, imm", Signed, Mips1).unwrap(),
+    ]);
+
+    assert!(table.lookup("hlt", 0).unwrap().word == 0xFC000000);
+    assert!(table.lookup("lw", 3).unwrap().operands.len() == 3);
+    assert!(table.lookup("sw", 2).is_some());
+    assert!(table.disassemble(0xFC000000, 0x0).as_deref() == Some("hlt"));
+    assert!(table.disassemble(0x8FA90004, 0x0).as_deref() == Some("lw $t1, $sp, 4"));
+}
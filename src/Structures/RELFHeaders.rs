@@ -1,3 +1,27 @@
+type RelfHeaderTuple = (
+    u32,
+    u8,
+    u8,
+    u8,
+    u8,
+    u8,
+    std::vec::Vec<u8>,
+    u16,
+    u16,
+    u32,
+    u32,
+    u32,
+    u32,
+    u32,
+    u16,
+    u16,
+    u16,
+    u16,
+    u16,
+    u16,
+);
+type SectionHeaderTuple = (u32, u32, u32, u32, u32, u32, u32, u32);
+
 #[derive(Debug)]
 pub struct RelfHeader32 {
     pub e_ident_MAG: u32,
@@ -51,30 +75,7 @@ impl Default for RelfHeader32 {
 }
 
 impl RelfHeader32 {
-    fn from_tuple(
-        tuple: (
-            u32,
-            u8,
-            u8,
-            u8,
-            u8,
-            u8,
-            std::vec::Vec<u8>,
-            u16,
-            u16,
-            u32,
-            u32,
-            u32,
-            u32,
-            u32,
-            u16,
-            u16,
-            u16,
-            u16,
-            u16,
-            u16,
-        ),
-    ) -> RelfHeader32 {
+    fn from_tuple(tuple: RelfHeaderTuple) -> RelfHeader32 {
         RelfHeader32 {
             e_ident_MAG: tuple.0,
             e_ident_CLASS: tuple.1,
@@ -100,127 +101,35 @@ impl RelfHeader32 {
     }
 }
 
-impl
-    From<(
-        u32,
-        u8,
-        u8,
-        u8,
-        u8,
-        u8,
-        std::vec::Vec<u8>,
-        u16,
-        u16,
-        u32,
-        u32,
-        u32,
-        u32,
-        u32,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-    )> for RelfHeader32
-{
-    fn from(
-        tpl: (
-            u32,
-            u8,
-            u8,
-            u8,
-            u8,
-            u8,
-            std::vec::Vec<u8>,
-            u16,
-            u16,
-            u32,
-            u32,
-            u32,
-            u32,
-            u32,
-            u16,
-            u16,
-            u16,
-            u16,
-            u16,
-            u16,
-        ),
-    ) -> Self {
+impl From<RelfHeaderTuple> for RelfHeader32 {
+    fn from(tpl: RelfHeaderTuple) -> Self {
         RelfHeader32::from_tuple(tpl)
     }
 }
 
-impl
-    Into<(
-        u32,
-        u8,
-        u8,
-        u8,
-        u8,
-        u8,
-        std::vec::Vec<u8>,
-        u16,
-        u16,
-        u32,
-        u32,
-        u32,
-        u32,
-        u32,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-    )> for RelfHeader32
-{
-    fn into(
-        self,
-    ) -> (
-        u32,
-        u8,
-        u8,
-        u8,
-        u8,
-        u8,
-        std::vec::Vec<u8>,
-        u16,
-        u16,
-        u32,
-        u32,
-        u32,
-        u32,
-        u32,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-        u16,
-    ) {
+impl From<RelfHeader32> for RelfHeaderTuple {
+    fn from(h: RelfHeader32) -> Self {
         (
-            self.e_ident_MAG,
-            self.e_ident_CLASS,
-            self.e_ident_DATA,
-            self.e_ident_VERSION,
-            self.e_ident_OSABI,
-            self.e_ident_ABIVERSION,
-            self.e_ident_EIPAD,
-            self.e_type,
-            self.e_machine,
-            self.e_version,
-            self.e_entry,
-            self.e_phoff,
-            self.e_shoff,
-            self.e_flags,
-            self.e_ehsize,
-            self.e_phentsize,
-            self.e_phnum,
-            self.e_shentsize,
-            self.e_shnum,
-            self.e_shstrndx,
+            h.e_ident_MAG,
+            h.e_ident_CLASS,
+            h.e_ident_DATA,
+            h.e_ident_VERSION,
+            h.e_ident_OSABI,
+            h.e_ident_ABIVERSION,
+            h.e_ident_EIPAD,
+            h.e_type,
+            h.e_machine,
+            h.e_version,
+            h.e_entry,
+            h.e_phoff,
+            h.e_shoff,
+            h.e_flags,
+            h.e_ehsize,
+            h.e_phentsize,
+            h.e_phnum,
+            h.e_shentsize,
+            h.e_shnum,
+            h.e_shstrndx,
         )
     }
 }
@@ -245,7 +154,7 @@ pub enum Sections {
 }
 
 impl SectionHeader32 {
-    fn from_tuple(tuple: (u32, u32, u32, u32, u32, u32, u32, u32)) -> SectionHeader32 {
+    fn from_tuple(tuple: SectionHeaderTuple) -> SectionHeader32 {
         SectionHeader32 {
             p_type: tuple.0,
             p_offset: tuple.1,
@@ -259,23 +168,16 @@ impl SectionHeader32 {
     }
 }
 
-impl From<(u32, u32, u32, u32, u32, u32, u32, u32)> for SectionHeader32 {
-    fn from(tpl: (u32, u32, u32, u32, u32, u32, u32, u32)) -> Self {
+impl From<SectionHeaderTuple> for SectionHeader32 {
+    fn from(tpl: SectionHeaderTuple) -> Self {
         SectionHeader32::from_tuple(tpl)
     }
 }
 
-impl Into<(u32, u32, u32, u32, u32, u32, u32, u32)> for SectionHeader32 {
-    fn into(self) -> (u32, u32, u32, u32, u32, u32, u32, u32) {
+impl From<SectionHeader32> for SectionHeaderTuple {
+    fn from(h: SectionHeader32) -> Self {
         (
-            self.p_type,
-            self.p_offset,
-            self.p_vaddr,
-            self.p_paddr,
-            self.p_filesz,
-            self.p_memsz,
-            self.p_flags,
-            self.p_align,
+            h.p_type, h.p_offset, h.p_vaddr, h.p_paddr, h.p_filesz, h.p_memsz, h.p_flags, h.p_align,
         )
     }
 }
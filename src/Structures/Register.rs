@@ -0,0 +1,153 @@
+use super::Errors::SyntaxError;
+
+/**
+ *  ABI names of the general purpose registers, indexed by register number
+ */
+const ABI_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+/**
+ *  A general purpose register. Can only hold numbers 0 through 31
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Register(u8);
+
+impl Register {
+    pub const ZERO: Register = Register(0);
+    pub const AT: Register = Register(1);
+    pub const RA: Register = Register(31);
+
+    /**
+     *  Parses a register name without its leading '$': either its number, `0` to `31`,
+     *  or its ABI name. `s8` is accepted as an alias of `fp`
+     */
+    pub fn parse(name: &str) -> Result<Self, SyntaxError> {
+        let lower = name.to_ascii_lowercase();
+        let number = match lower.as_str() {
+            "s8" => Some(30),
+            n if n.starts_with(|c: char| c.is_ascii_digit()) => {
+                // "01" or "+1" are not register names
+                match n.parse::<u8>() {
+                    Ok(v) if v < 32 && v.to_string() == n => Some(v),
+                    _ => None,
+                }
+            }
+            n => ABI_NAMES.iter().position(|abi| *abi == n).map(|v| v as u8),
+        };
+        number
+            .map(Register)
+            .ok_or_else(|| SyntaxError::UnknownRegister(name.into()))
+    }
+
+    /**
+     *  General purpose register encoded in the low 5 bits of an instruction field, as
+     *  read back by the disassembler
+     */
+    pub fn from_bits(bits: u32) -> Self {
+        Register((bits & 0x1F) as u8)
+    }
+
+    pub fn number(self) -> u32 {
+        self.0 as u32
+    }
+}
+
+/**
+ *  Symbolic names of the R3000 system control (CP0) registers
+ */
+const CP0_NAMES: [(&str, u8); 13] = [
+    ("index", 0),
+    ("random", 1),
+    ("entrylo", 2),
+    ("context", 4),
+    ("badvaddr", 8),
+    ("count", 9),
+    ("entryhi", 10),
+    ("compare", 11),
+    ("status", 12),
+    ("sr", 12),
+    ("cause", 13),
+    ("epc", 14),
+    ("prid", 15),
+];
+
+/**
+ *  A coprocessor register. Coprocessor operands may also be given by number, in which case
+ *  the parser sees a general purpose `Register` and `from_number` converts it
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CopRegister(u8);
+
+impl CopRegister {
+    /**
+     *  Parses a symbolic CP0 register name without its leading '$', such as `status`
+     */
+    pub fn parse_cp0(name: &str) -> Option<Self> {
+        let lower = name.to_ascii_lowercase();
+        CP0_NAMES
+            .iter()
+            .find(|(cp0, _)| *cp0 == lower)
+            .map(|(_, v)| CopRegister(*v))
+    }
+
+    /**
+     *  Parses a floating point register name without its leading '$', `f0` to `f31`
+     */
+    pub fn parse_fpr(name: &str) -> Option<Self> {
+        let lower = name.to_ascii_lowercase();
+        let n = lower.strip_prefix('f')?;
+        match n.parse::<u8>() {
+            Ok(v) if v < 32 && v.to_string() == n => Some(CopRegister(v)),
+            _ => None,
+        }
+    }
+
+    pub fn from_number(r: Register) -> Self {
+        CopRegister(r.0)
+    }
+
+    /**
+     *  Coprocessor register in the low 5 bits of `bits`, whichever of fs, ft, fd or a cop0
+     *  register field they were taken from
+     */
+    pub fn from_bits(bits: u32) -> Self {
+        CopRegister((bits & 0x1F) as u8)
+    }
+
+    pub fn number(self) -> u32 {
+        self.0 as u32
+    }
+}
+
+impl std::fmt::Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "${}", ABI_NAMES[self.0 as usize])
+    }
+}
+
+#[test]
+fn register_names() {
+    assert!(Register::parse("0").unwrap() == Register::ZERO);
+    assert!(Register::parse("zero").unwrap() == Register::ZERO);
+    assert!(Register::parse("31").unwrap().number() == 31);
+    assert!(Register::parse("RA").unwrap() == Register::RA);
+    assert!(Register::parse("t8").unwrap().number() == 24);
+    assert!(Register::parse("s8").unwrap() == Register::parse("fp").unwrap());
+    assert!(Register::parse("fp").unwrap().to_string() == "$fp");
+
+    for bad in ["32", "99", "01", "t10", "f0", "pc", "status"] {
+        assert!(Register::parse(bad).is_err(), "{bad}");
+    }
+
+    assert!(CopRegister::parse_cp0("Status").unwrap().number() == 12);
+    assert!(CopRegister::parse_cp0("sr") == CopRegister::parse_cp0("status"));
+    assert!(CopRegister::parse_cp0("t0").is_none());
+
+    assert!(CopRegister::parse_fpr("F31").unwrap().number() == 31);
+    for bad in ["f32", "f01", "fp", "f"] {
+        assert!(CopRegister::parse_fpr(bad).is_none(), "{bad}");
+    }
+}
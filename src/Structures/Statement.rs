@@ -0,0 +1,37 @@
+use super::Diagnostic::Span;
+use super::Expression::Expr;
+use super::Register::{CopRegister, Register};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperandKind {
+    Register(Register),
+    CopRegister(CopRegister), // only symbolic names, numbered ones are parsed as Register
+    FpRegister(CopRegister),
+    Imm(i64),
+    Label(String),
+    Memory(Box<OperandKind>, Register), // offset(base), the offset is an Imm, Label, Expr, Hi or Lo
+    Str(String),
+    Expr(Expr), // an expression that refers to tags, constant ones are folded into Imm
+    Hi(Expr),   // %hi(x), the upper half adjusted for a signed %lo
+    Lo(Expr),   // %lo(x)
+}
+
+#[derive(Debug, Clone)]
+pub struct Operand {
+    pub kind: OperandKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Label(String),
+    Directive(String, Vec<Operand>), // name includes the leading '.'
+    Instruction(String, Vec<Operand>),
+    Invalid, // an instruction that failed to parse, it still takes up its slot
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
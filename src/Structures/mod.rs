@@ -1,6 +1,10 @@
 pub mod ArgumentBundle;
 pub mod Data;
+pub mod Diagnostic;
 pub mod Errors;
+pub mod Expression;
 pub mod Instruction;
-pub mod Opcodes;
+pub mod InstructionTable;
 pub mod RELFHeaders;
+pub mod Register;
+pub mod Statement;
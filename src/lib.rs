@@ -0,0 +1,13 @@
+extern crate core;
+extern crate serde;
+extern crate serde_json;
+extern crate structure;
+extern crate toml;
+
+#[allow(non_snake_case)]
+pub mod Functionality;
+#[allow(non_snake_case)]
+pub mod Structures;
+
+pub use Functionality::Assembler::{AssembledProgram, Assembler};
+pub use Structures::Instruction::IsaLevel;
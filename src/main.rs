@@ -1,26 +1,13 @@
-#![feature(let_chains)]
-
-#[macro_use]
-extern crate lazy_static;
 extern crate clap;
-extern crate core;
-extern crate mut_static;
-extern crate structure;
-
-#[allow(non_snake_case)]
-mod Functionality;
-#[allow(non_snake_case)]
-mod Structures;
+extern crate mips_compiler;
 
 use clap::Parser;
 use std::fs;
 use std::process::exit;
 use std::time::Instant;
 
-use Functionality::{CodeGen, Preprocess, TagResolution};
-use Structures::Data::Data;
-use Structures::Instruction::Instruction;
-use Structures::RELFHeaders::Sections;
+use mips_compiler::Functionality::Lexer::parse_int_hex_or_dec;
+use mips_compiler::{Assembler, IsaLevel};
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -39,84 +26,104 @@ struct Args {
         required = true
     )]
     output: String,
+    #[clap(
+        long = "isa",
+        help = "Instruction set level: mips1, mips2 or mips32",
+        default_value = "mips1"
+    )]
+    isa: IsaLevel,
+    #[clap(
+        short = 'x',
+        long = "extension",
+        help = "TOML or JSON file with extra instruction definitions",
+        multiple_occurrences = true
+    )]
+    extensions: Vec<String>,
+    #[clap(
+        short = 'I',
+        long = "include-dir",
+        help = "Directory to search for .include files",
+        multiple_occurrences = true
+    )]
+    include_dirs: Vec<String>,
+    #[clap(
+        short = 'D',
+        long = "define",
+        help = "Predefine a symbol as NAME or NAME=VALUE, NAME alone is 1",
+        parse(try_from_str = parse_define),
+        multiple_occurrences = true
+    )]
+    defines: Vec<(String, i64)>,
+}
+
+fn parse_define(s: &str) -> Result<(String, i64), String> {
+    match s.split_once('=') {
+        Some((name, value)) => match parse_int_hex_or_dec(value) {
+            // constants are 32b, signed or not
+            Ok(v) if (i32::MIN as i64..=u32::MAX as i64).contains(&v) => Ok((name.to_string(), v)),
+            Ok(v) => Err(format!("{v} does not fit in 32b")),
+            Err(e) => Err(e.to_string()),
+        },
+        None => Ok((s.to_string(), 1)),
+    }
+}
+
+fn read_or_exit(path: &str, what: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("error: could not read {what} file \"{path}\": {e}");
+        exit(-1)
+    })
 }
 
 fn main() {
     let args = Args::parse();
 
     let timer = Instant::now();
-    //initialize the tag_resolutor
-    Functionality::TagResolution::init();
-
-    let fs = fs::read_to_string(args.input).unwrap();
-    let code_digest = Preprocess::digest(&fs);
-
-    let mut addr: u32 = 0; //a segment start has to be processed before any instructions
-    let mut instr_v: Vec<((String, usize), Option<Instruction>)> = Vec::new();
-    let mut data_v: Vec<((String, usize), Option<Data>)> = Vec::new();
 
-    let mut is_code_segment = false;
-    let mut code_base_addr = 0;
-    let mut data_base_addr = 0;
+    let fs = read_or_exit(&args.input, "input");
 
-    let mut line_count = 1;
-    //preprocessing
-    for (original_line, processed) in code_digest {
-        let mut instr: Option<Instruction> = None;
-        let mut data: Option<Data> = None;
-        //dbg!(&processed);
-        match processed {
-            Preprocess::LineTag::Processed(l) => {
-                if is_code_segment {
-                    instr = Some(l.into())
-                };
-
-                addr += 0x4;
-            }
-            Preprocess::LineTag::Ignore => {
-                instr = None;
-            }
-            Preprocess::LineTag::Tag(tag) => {
-                instr = None;
-                //are we on data? if so, they are categorized as tag
-                if !is_code_segment {
-                    data = Some(String::from(&original_line).into())
-                }
-                TagResolution::log_addr(tag, addr);
-            }
-            Preprocess::LineTag::SectionStart(a, section_type) => {
-                addr = a;
-                match section_type {
-                    Sections::Code => {
-                        is_code_segment = true;
-                        code_base_addr = a;
-                    }
-                    Sections::Data => {
-                        is_code_segment = false;
-                        data_base_addr = a;
-                    } //_ => is_code_segment = false,
-                }
-            }
-        }
-
-        if is_code_segment {
-            instr_v.push(((original_line, line_count), instr));
-        } else {
-            data_v.push(((original_line, line_count), data))
+    let mut assembler = Assembler::new();
+    assembler.isa(args.isa);
+    for (name, value) in &args.defines {
+        assembler.define(name, *value);
+    }
+    for dir in &args.include_dirs {
+        assembler.include_dir(dir);
+    }
+    for ext in &args.extensions {
+        let text = read_or_exit(ext, "extension");
+        if let Err(e) = assembler.extend_isa(ext, &text) {
+            eprintln!("{}", e);
+            exit(-1)
         }
-        line_count += 1;
     }
 
-    //compilation
-    let (code, data) = match CodeGen::compile(code_base_addr, instr_v, data_base_addr, data_v) {
-        Ok((c, v)) => (c, v),
-        Err(e) => {
-            eprintln!("{}", e);
+    let program = match assembler.add_source(&args.input, &fs).assemble() {
+        Ok(p) => p,
+        Err(diagnostics) => {
+            for d in &diagnostics {
+                eprintln!("{}", d);
+            }
+            let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+            eprintln!(
+                "error: could not assemble \"{}\" due to {} previous error{}",
+                args.input,
+                errors,
+                if errors == 1 { "" } else { "s" }
+            );
             exit(-1)
         }
     };
 
-    CodeGen::pack_and_write(args.output, code_base_addr, code, data_base_addr, data);
+    for w in &program.warnings {
+        eprintln!("{}", w);
+    }
+
+    for line in &program.listing {
+        println!("{}", line);
+    }
+
+    program.write(args.output);
 
     println!("\nFinished in {:?}", timer.elapsed());
 }